@@ -0,0 +1,126 @@
+//! Blocking API.
+//! It mirrors the async one and runs it on a runtime it manages itself, like `reqwest::blocking` does.
+//! Its functions must not be called from within an async runtime, as they would panic.
+
+use crate::cf_ips::CFIPs;
+use crate::checker::{self, CheckerBuilder, RunSummary};
+use crate::domain::Domain;
+use std::{error::Error, ops::Deref, sync::Arc};
+use tokio::runtime::Runtime;
+
+/// A blocking checker. The async checker's results are reachable through `Deref`.
+#[derive(Debug)]
+pub struct Checker {
+    inner: checker::Checker,
+    runtime: Runtime,
+}
+
+impl Checker {
+    /// Build a new checker instance.
+    /// The function takes one or several domains separated by newline characters as input
+    /// #Example:
+    /// ```no_run
+    /// use cfd::blocking::Checker;
+    /// let checker = Checker::build("example.com\ncloudflare.com".to_string());
+    /// assert!(checker.is_ok());
+    /// ```
+    pub fn build(target: String) -> Result<Self, Box<dyn Error>> {
+        Checker::from_builder(checker::Checker::builder().targets(&target))
+    }
+
+    /// Build a new checker instance which runs only the given detectors.
+    /// The detectors are a bit mask of `check_result` bits.
+    pub fn build_with_detectors(target: String, detectors: u8) -> Result<Self, Box<dyn Error>> {
+        Checker::from_builder(
+            checker::Checker::builder()
+                .targets(&target)
+                .detectors(detectors),
+        )
+    }
+
+    /// Build a new checker instance configured by a builder.
+    /// #Example:
+    /// ```
+    /// use cfd::{blocking, cf_ips::CFIPs, checker::Checker};
+    /// let builder = Checker::builder().cf_ips(CFIPs::parse("104.16.0.0/13"));
+    /// let mut checker = blocking::Checker::from_builder(builder).unwrap();
+    /// let summary = checker.check().unwrap();
+    /// assert_eq!(summary.total(), 0);
+    /// assert!(checker.detected().is_empty());
+    /// ```
+    pub fn from_builder(builder: CheckerBuilder) -> Result<Self, Box<dyn Error>> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(builder.build())?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Starts a check to determine if domains are behind CF and waits for it to finish.
+    /// #Example:
+    /// ```no_run
+    /// use cfd::blocking::Checker;
+    /// let mut checker = Checker::build("cloudflare.com".to_string()).unwrap();
+    /// checker.check().unwrap();
+    /// assert_eq!(checker.detected().len(), 1);
+    /// ```
+    pub fn check(&mut self) -> Result<RunSummary, Box<dyn Error>> {
+        self.runtime.block_on(self.inner.check())
+    }
+
+    /// Consumes the blocking checker and returns the async one.
+    pub fn into_inner(self) -> checker::Checker {
+        self.inner
+    }
+}
+
+impl Deref for Checker {
+    type Target = checker::Checker;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+/// Runs the checker and waits for it to finish.
+/// #Example:
+/// ```no_run
+/// use cfd::blocking;
+/// let (checker, summary) = blocking::run("example.com\ncloudflare.com".to_string()).unwrap();
+/// assert_eq!(checker.detected().len(), 1);
+/// assert_eq!(summary.total(), 2);
+/// ```
+pub fn run(target: String) -> Result<(Checker, RunSummary), Box<dyn Error>> {
+    let mut checker = Checker::build(target)?;
+    let summary = checker.check()?;
+    Ok((checker, summary))
+}
+
+/// Loads CF's IP ranges.
+/// #Example:
+/// ```no_run
+/// use cfd::blocking;
+/// let cf_ips = blocking::load_cf_ips().unwrap();
+/// assert!(cf_ips.check_ip_v4("131.0.72.1"));
+/// ```
+pub fn load_cf_ips() -> Result<CFIPs, Box<dyn Error>> {
+    runtime()?.block_on(CFIPs::load())
+}
+
+/// Checks the domain for signs to see if it is behind CF.
+/// #Example:
+/// ```no_run
+/// use cfd::{blocking, domain::Domain};
+/// use std::sync::Arc;
+/// let cf_ips = Arc::new(blocking::load_cf_ips().unwrap());
+/// let mut domain = Domain::build("cloudflare.com".to_string()).unwrap();
+/// blocking::verify_domain(&mut domain, cf_ips).unwrap();
+/// assert_eq!(domain.check_result, 0b11111);
+/// ```
+pub fn verify_domain(domain: &mut Domain, cf_ips: Arc<CFIPs>) -> Result<(), Box<dyn Error>> {
+    runtime()?.block_on(domain.verify_domain(cf_ips))
+}
+
+fn runtime() -> Result<Runtime, Box<dyn Error>> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
@@ -1,7 +1,14 @@
 //! Cloudflare IP ranges provider.
 //! It's main function is to load and provide a list of Cloudflare IP ranges.
 
-use crate::helpers::{split_to_string_vec, string_to_binary};
+use crate::cidr::Cidr;
+use crate::transport::{HttpTransport, Transport};
+use std::net::IpAddr;
+
+/// Where CF lists its IPv4 ranges.
+pub const IPS_V4_URL: &str = "https://www.cloudflare.com/ips-v4";
+/// Where CF lists its IPv6 ranges.
+pub const IPS_V6_URL: &str = "https://www.cloudflare.com/ips-v6";
 
 #[derive(Debug)]
 pub struct CFIPs {
@@ -11,16 +18,74 @@ pub struct CFIPs {
 
 impl CFIPs {
     pub async fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let ipsv4 = Self::load_ips("https://www.cloudflare.com/ips-v4").await?;
-        let ipsv6 = Self::load_ips("https://www.cloudflare.com/ips-v6").await?;
+        Self::load_with(&HttpTransport::default()).await
+    }
+
+    /// Loads CF's IP ranges through the given transport.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::{CFIPs, IPS_V4_URL, IPS_V6_URL}, transport::{HttpResponse, MockTransport}};
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let transport = MockTransport::new()
+    ///         .with_response(IPS_V4_URL, HttpResponse::new(200).body("173.245.48.0/20\n103.21.244.0/22"))
+    ///         .with_response(IPS_V6_URL, HttpResponse::new(200).body("2400:cb00::/32"));
+    ///     let cf_ips = CFIPs::load_with(&transport).await.unwrap();
+    ///     assert_eq!(cf_ips.ipsv4.len(), 2);
+    ///     assert_eq!(cf_ips.ipsv6, vec!["2400:cb00::/32"]);
+    /// }
+    /// ```
+    pub async fn load_with(transport: &dyn Transport) -> Result<Self, Box<dyn std::error::Error>> {
+        let ipsv4 = Self::load_ips(transport, IPS_V4_URL).await?;
+        let ipsv6 = Self::load_ips(transport, IPS_V6_URL).await?;
         Ok(Self { ipsv4, ipsv6 })
     }
 
-    async fn load_ips(url: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        Ok(split_to_string_vec(
-            reqwest::get(url).await?.text().await?,
-            "\n",
-        ))
+    /// Parses ranges listed one per line, e.g. a snapshot made by `cfd ranges export --format plain`.
+    /// Lines that aren't valid CIDRs are skipped.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs::parse("173.245.48.0/20\n2400:cb00::/32\n# comment\n");
+    /// assert_eq!(cf_ips.ipsv4, vec!["173.245.48.0/20"]);
+    /// assert_eq!(cf_ips.ipsv6, vec!["2400:cb00::/32"]);
+    /// ```
+    pub fn parse(ranges: &str) -> Self {
+        let (ipsv4, ipsv6): (Vec<Cidr>, Vec<Cidr>) = ranges
+            .lines()
+            .filter_map(|line| line.parse::<Cidr>().ok())
+            .partition(|cidr| cidr.is_ipv4());
+        Self {
+            ipsv4: ipsv4.iter().map(|cidr| cidr.to_string()).collect(),
+            ipsv6: ipsv6.iter().map(|cidr| cidr.to_string()).collect(),
+        }
+    }
+
+    async fn load_ips(
+        transport: &dyn Transport,
+        url: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let resp = transport
+            .get(url)
+            .await
+            .map_err(|e| e as Box<dyn std::error::Error>)?;
+        if !resp.is_success() {
+            return Err(format!(
+                "Failed to load CF IP ranges from {}: HTTP {}",
+                url, resp.status
+            )
+            .into());
+        }
+        let ranges: Vec<String> = resp
+            .body
+            .lines()
+            .filter(|line| line.parse::<Cidr>().is_ok())
+            .map(|line| line.to_string())
+            .collect();
+        if ranges.is_empty() {
+            return Err(format!("No CF IP ranges found at {}", url).into());
+        }
+        Ok(ranges)
     }
 }
 
@@ -29,11 +94,9 @@ impl CFIPs {
     /// #Example:
     /// ```
     /// use cfd::cf_ips::CFIPs;
-    /// #[tokio::main]
-    /// async fn main(){
-    ///     let cf_ips = CFIPs::load().await.unwrap();
-    ///     assert!(cf_ips.check_ip_v4("131.0.72.1"));
-    /// }
+    /// let cf_ips = CFIPs::parse("131.0.72.0/22");
+    /// assert!(cf_ips.check_ip_v4("131.0.72.1"));
+    /// assert!(!cf_ips.check_ip_v4("192.0.2.1"));
     /// ```
     ///
     pub fn check_ip_v4(&self, ip: &str) -> bool {
@@ -42,38 +105,228 @@ impl CFIPs {
             .any(|cidr| CFIPs::check_ip_in_cidr(ip, cidr))
     }
 
-    /// Checks if an IPv4 address is in a given CIDR range
+    /// Checks if an IPv4 or IPv6 address is CF's.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs {
+    ///     ipsv4: vec!["104.16.0.0/13".to_string()],
+    ///     ipsv6: vec!["2606:4700::/32".to_string()],
+    /// };
+    /// assert!(cf_ips.check_ip(&"2606:4700::6810:84e5".parse().unwrap()));
+    /// assert!(!cf_ips.check_ip(&"104.24.0.1".parse().unwrap()));
+    /// ```
+    ///
+    pub fn check_ip(&self, ip: &IpAddr) -> bool {
+        let ranges = match ip {
+            IpAddr::V4(_) => &self.ipsv4,
+            IpAddr::V6(_) => &self.ipsv6,
+        };
+        let ip = ip.to_string();
+        ranges
+            .iter()
+            .any(|cidr| CFIPs::check_ip_in_cidr(ip.as_str(), cidr))
+    }
+
+    /// Checks if an IP address is in a given CIDR range
     /// #Example:
     /// ```
     /// use cfd::cf_ips::CFIPs;
     /// assert!(CFIPs::check_ip_in_cidr("131.0.72.1", "131.0.72.0/22"));
+    /// assert!(CFIPs::check_ip_in_cidr("2a06:98c1::1", "2a06:98c0::/29"));
+    /// assert!(!CFIPs::check_ip_in_cidr("131.0.76.1", "131.0.72.0/22"));
     /// ```
     ///
     pub fn check_ip_in_cidr(ip: &str, cidr_range: &str) -> bool {
-        // parse ip and cidr range
-        let ip_parts: Vec<&str> = ip.split(".").collect();
-        let cidr_parts: Vec<&str> = cidr_range.split("/").collect();
-        let mask_size: u8 = cidr_parts[1].parse().unwrap_or(0);
-
-        // check if ip and cidr have the same network class
-        if ip_parts.len() != 4 || cidr_parts[0].split(".").collect::<Vec<&str>>().len() != 4 {
-            return false;
+        match (ip.trim().parse::<IpAddr>(), cidr_range.parse::<Cidr>()) {
+            (Ok(ip), Ok(cidr)) => cidr.contains_ip(&ip),
+            _ => false,
         }
+    }
+}
+
+/// Ranges added and removed between two sets of CF's ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RangesDiff {
+    /// Ranges which are new.
+    pub added: Vec<Cidr>,
+    /// Ranges which are gone.
+    pub removed: Vec<Cidr>,
+}
+
+impl RangesDiff {
+    /// Checks if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// How an IP or a CIDR relates to CF's ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Membership {
+    /// Entirely covered by the listed ranges.
+    Inside(Vec<Cidr>),
+    /// Partially covered by the listed ranges.
+    Partial(Vec<Cidr>),
+    /// Not covered by any range.
+    Outside,
+}
+
+impl CFIPs {
+    /// Compares the ranges with a previous set of them, e.g. a stored snapshot.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let snapshot = CFIPs::parse("173.245.48.0/20\n103.21.244.0/22");
+    /// let fresh = CFIPs::parse("173.245.48.0/20\n2400:cb00::/32");
+    /// let diff = fresh.diff(&snapshot);
+    /// assert_eq!(diff.added, vec!["2400:cb00::/32".parse().unwrap()]);
+    /// assert_eq!(diff.removed, vec!["103.21.244.0/22".parse().unwrap()]);
+    /// assert!(fresh.diff(&fresh).is_empty());
+    /// ```
+    pub fn diff(&self, other: &CFIPs) -> RangesDiff {
+        let (ranges, other_ranges) = (self.ranges(), other.ranges());
+        RangesDiff {
+            added: ranges
+                .iter()
+                .filter(|cidr| !other_ranges.contains(cidr))
+                .copied()
+                .collect(),
+            removed: other_ranges
+                .iter()
+                .filter(|cidr| !ranges.contains(cidr))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Returns all CF's ranges, IPv4 first, skipping lines that aren't valid CIDRs.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs {
+    ///     ipsv4: vec!["104.16.0.0/13".to_string(), "".to_string()],
+    ///     ipsv6: vec!["2606:4700::/32".to_string()],
+    /// };
+    /// assert_eq!(cf_ips.ranges().len(), 2);
+    /// ```
+    pub fn ranges(&self) -> Vec<Cidr> {
+        self.ipsv4
+            .iter()
+            .chain(self.ipsv6.iter())
+            .filter_map(|cidr| cidr.parse().ok())
+            .collect()
+    }
+
+    /// Returns CF's ranges with adjacent and overlapping ones merged.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs::parse("104.16.0.0/13\n104.24.0.0/14\n104.28.0.0/14\n");
+    /// assert_eq!(cf_ips.aggregate(), vec!["104.16.0.0/12".parse().unwrap()]);
+    /// ```
+    pub fn aggregate(&self) -> Vec<Cidr> {
+        Cidr::aggregate(&self.ranges())
+    }
+
+    /// Returns the number of distinct addresses in CF's ranges.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs::parse("173.245.48.0/20\n103.21.244.0/22\n");
+    /// assert_eq!(cf_ips.address_count(), 4096 + 1024);
+    /// ```
+    pub fn address_count(&self) -> u128 {
+        Cidr::count_addresses(&self.ranges())
+    }
 
-        // convert ip and cidr to binary strings
-        let ip_bin = ip_parts
+    /// Checks if a CIDR is entirely covered by CF's ranges.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs::parse("104.16.0.0/13\n104.24.0.0/14\n");
+    /// assert!(cf_ips.contains_cidr(&"104.20.0.0/16".parse().unwrap()));
+    /// assert!(!cf_ips.contains_cidr(&"104.16.0.0/12".parse().unwrap()));
+    /// ```
+    pub fn contains_cidr(&self, cidr: &Cidr) -> bool {
+        matches!(self.membership(cidr), Membership::Inside(_))
+    }
+
+    /// Checks if a CIDR shares at least one address with CF's ranges.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs::parse("104.16.0.0/13\n");
+    /// assert!(cf_ips.overlaps_cidr(&"104.0.0.0/8".parse().unwrap()));
+    /// assert!(!cf_ips.overlaps_cidr(&"10.0.0.0/8".parse().unwrap()));
+    /// ```
+    pub fn overlaps_cidr(&self, cidr: &Cidr) -> bool {
+        self.membership(cidr) != Membership::Outside
+    }
+
+    /// Returns the parts of the given CIDRs which are in CF's ranges, merged.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs::parse("104.16.0.0/13\n2606:4700::/32\n");
+    /// let vpc = vec!["104.18.0.0/16".parse().unwrap(), "10.0.0.0/8".parse().unwrap()];
+    /// assert_eq!(cf_ips.intersection(&vpc), vec!["104.18.0.0/16".parse().unwrap()]);
+    /// ```
+    pub fn intersection(&self, cidrs: &[Cidr]) -> Vec<Cidr> {
+        let ranges = self.ranges();
+        let common = cidrs
             .iter()
-            .map(|x| string_to_binary(x))
-            .collect::<String>();
-        let cidr_bin = cidr_parts[0]
-            .split(".")
-            .map(|x| string_to_binary(x))
-            .collect::<String>();
-
-        // check if the first n bits of the ip match the first n bits of the cidr range
-        if ip_bin[0..mask_size as usize] == cidr_bin[0..mask_size as usize] {
-            return true;
+            .flat_map(|cidr| ranges.iter().filter_map(|range| range.intersection(cidr)))
+            .collect::<Vec<Cidr>>();
+        Cidr::aggregate(&common)
+    }
+
+    /// Returns CF's range an IP belongs to.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::CFIPs;
+    /// let cf_ips = CFIPs { ipsv4: vec!["104.16.0.0/13".to_string()], ipsv6: vec![] };
+    /// let range = cf_ips.find_range(&"104.17.1.1".parse().unwrap()).unwrap();
+    /// assert_eq!(range.to_string(), "104.16.0.0/13");
+    /// ```
+    pub fn find_range(&self, ip: &IpAddr) -> Option<Cidr> {
+        self.ranges()
+            .into_iter()
+            .find(|range| range.contains_ip(ip))
+    }
+
+    /// Checks how a CIDR relates to CF's ranges.
+    /// A CIDR is inside if one range contains it or the ranges it spans cover all of its addresses.
+    /// #Example:
+    /// ```
+    /// use cfd::cf_ips::{CFIPs, Membership};
+    /// let cf_ips = CFIPs {
+    ///     ipsv4: vec!["104.16.0.0/13".to_string(), "104.24.0.0/14".to_string()],
+    ///     ipsv6: vec![],
+    /// };
+    /// let inside = cf_ips.membership(&"104.16.0.0/16".parse().unwrap());
+    /// assert_eq!(inside, Membership::Inside(vec!["104.16.0.0/13".parse().unwrap()]));
+    /// let partial = cf_ips.membership(&"104.0.0.0/8".parse().unwrap());
+    /// assert_eq!(partial, Membership::Partial(cf_ips.ranges()));
+    /// assert_eq!(cf_ips.membership(&"1.1.1.1".parse().unwrap()), Membership::Outside);
+    /// ```
+    pub fn membership(&self, cidr: &Cidr) -> Membership {
+        let ranges = self.ranges();
+        if let Some(range) = ranges.iter().find(|range| range.contains(cidr)) {
+            return Membership::Inside(vec![*range]);
+        }
+        let overlapping = ranges
+            .into_iter()
+            .filter(|range| range.overlaps(cidr))
+            .collect::<Vec<Cidr>>();
+        // Ranges that don't contain the CIDR but overlap it lie inside it.
+        let covered = Cidr::count_addresses(&overlapping);
+        if overlapping.is_empty() {
+            Membership::Outside
+        } else if covered >= cidr.size() {
+            Membership::Inside(overlapping)
+        } else {
+            Membership::Partial(overlapping)
         }
-        false
     }
 }
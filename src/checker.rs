@@ -1,21 +1,326 @@
 //! This structure joins domains and cf_ips together to execute checking tasks concurrently.
 
 use crate::cf_ips::CFIPs;
-use crate::domain::Domain;
-use std::{error::Error, sync::Arc};
-use tokio::sync::Mutex;
+use crate::domain::{check_result, Domain};
+use crate::probe::{ClientOptions, Probe, ProbePolicy};
+use crate::targets::{Rejected, Targets};
+use crate::transport::Transport;
+#[cfg(feature = "public-suffix")]
+use std::fmt;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc::UnboundedSender, Semaphore};
+
+/// The default number of domains checked at once.
+pub const DEFAULT_CONCURRENCY: usize = 64;
 
 #[derive(Debug)]
 pub struct Checker {
-    pub domains: Vec<Arc<Mutex<Domain>>>,
+    reports: Vec<DomainReport>,
+    rejected: Vec<Rejected>,
     pub cf_ips: Arc<CFIPs>,
+    probe: Arc<Probe>,
+    concurrency: usize,
+}
+
+/// A checked domain together with the error its check failed with, if any.
+#[derive(Debug, Clone)]
+pub struct DomainReport {
+    /// The domain with its check results.
+    pub domain: Domain,
+    /// The error the check failed with.
+    pub error: Option<String>,
+}
+
+impl DomainReport {
+    /// Checks if the domain is reachable and behind CF.
+    /// Exposed origins aren't, even if some of CF's signs are detected.
+    pub fn is_detected(&self) -> bool {
+        !self.domain.is_unreachable
+            && !self.domain.has_cf_origin_ca()
+            && self.domain.check_result & check_result::DETECTED != 0
+    }
+
+    /// Checks if the domain is reachable and serves a CF Origin CA certificate directly, i.e. it's an exposed origin.
+    pub fn is_exposed(&self) -> bool {
+        !self.domain.is_unreachable && self.domain.has_cf_origin_ca()
+    }
+}
+
+/// A progress update sent each time a domain has been checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of checked domains.
+    pub done: usize,
+    /// The number of domains to check.
+    pub total: usize,
+    /// The domain which has just been checked, with the port if it has one.
+    pub domain: String,
+}
+
+/// A summary of a check.
+#[derive(Debug, Clone, Default)]
+pub struct RunSummary {
+    /// How long the whole check took.
+    pub duration: Duration,
+    /// How long the check of each domain took, including the wait for a free slot. Keyed by `Domain::target`.
+    pub durations: HashMap<String, Duration>,
+    /// The number of domains behind CF.
+    pub detected: usize,
+    /// The number of exposed origins: domains serving a CF Origin CA certificate directly.
+    pub exposed: usize,
+    /// The number of reachable domains without CF presence.
+    pub not_detected: usize,
+    /// The number of unreachable domains.
+    pub unreachable: usize,
+    /// Domains which checks failed, with the errors.
+    pub errors: Vec<(String, String)>,
+}
+
+impl RunSummary {
+    /// Returns the number of checked domains.
+    pub fn total(&self) -> usize {
+        self.detected + self.exposed + self.not_detected + self.unreachable
+    }
+}
+
+/// How many hosts of a registrable domain (eTLD+1) are behind CF.
+/// A host is an exposed origin if any of its targets is, behind CF if any of them is, and unreachable if all of them are.
+#[cfg(feature = "public-suffix")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApexCoverage {
+    /// The registrable domain in Unicode, or the host itself if it's a public suffix.
+    pub apex: String,
+    /// The number of checked hosts under the apex, the apex itself included.
+    pub hosts: usize,
+    /// The number of hosts behind CF.
+    pub detected: usize,
+    /// The number of hosts which are exposed origins.
+    pub exposed: usize,
+    /// The number of unreachable hosts.
+    pub unreachable: usize,
+}
+
+#[cfg(feature = "public-suffix")]
+impl ApexCoverage {
+    /// Returns the number of reachable hosts without CF presence.
+    pub fn not_detected(&self) -> usize {
+        self.hosts - self.detected - self.exposed - self.unreachable
+    }
+}
+
+#[cfg(feature = "public-suffix")]
+impl fmt::Display for ApexCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} hosts of {} are behind CF",
+            self.detected, self.hosts, self.apex
+        )
+    }
+}
+
+/// A builder to configure a checker without touching the network until it's run.
+/// CF's IP ranges are downloaded on `build` only if none were given.
+#[derive(Debug, Clone)]
+pub struct CheckerBuilder {
+    targets: Vec<String>,
+    ports: Vec<u16>,
+    cf_ips: Option<Arc<CFIPs>>,
+    policy: ProbePolicy,
+    detectors: u8,
+    concurrency: usize,
+    options: ClientOptions,
+    transport: Option<Arc<dyn Transport>>,
+}
+
+impl Default for CheckerBuilder {
+    fn default() -> Self {
+        Self {
+            targets: vec![],
+            ports: vec![],
+            cf_ips: None,
+            policy: ProbePolicy::default(),
+            detectors: check_result::DEFAULT,
+            concurrency: DEFAULT_CONCURRENCY,
+            options: ClientOptions::default(),
+            transport: None,
+        }
+    }
+}
+
+impl CheckerBuilder {
+    /// Adds one or several domains separated by newline characters.
+    pub fn targets(mut self, target: &str) -> Self {
+        self.targets
+            .extend(target.split('\n').map(|domain| domain.to_string()));
+        self
+    }
+
+    /// Checks each target on each of the ports, guessing the scheme from the port.
+    /// Targets given with a port, even the default one, are checked on it only. Each URL is checked once.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, checker::Checker, domain::ports};
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let checker = Checker::builder()
+    ///         .targets("example.com\nexample.net:8080")
+    ///         .ports(&ports::CF_HTTPS)
+    ///         .cf_ips(CFIPs::parse("104.16.0.0/13"))
+    ///         .build()
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(checker.reports().len(), ports::CF_HTTPS.len() + 1);
+    ///     assert_eq!(checker.reports()[1].domain.url(), "https://example.com:2053");
+    ///     assert_eq!(checker.reports()[6].domain.target(), "example.net:8080");
+    /// }
+    /// ```
+    pub fn ports(mut self, ports: &[u16]) -> Self {
+        self.ports.extend_from_slice(ports);
+        self
+    }
+
+    /// Uses the given CF's IP ranges instead of downloading them.
+    pub fn cf_ips(mut self, cf_ips: impl Into<Arc<CFIPs>>) -> Self {
+        self.cf_ips = Some(cf_ips.into());
+        self
+    }
+
+    /// Sets timeouts, retries and redirects of probes.
+    pub fn probe_policy(mut self, policy: ProbePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets the detectors to run, a bit mask of `check_result` bits.
+    pub fn detectors(mut self, detectors: u8) -> Self {
+        self.detectors = detectors;
+        self
+    }
+
+    /// Sets how many domains are checked at once. Zero is treated as one.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets the user agent of HTTP probes.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.options.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Sends HTTP probes through a proxy, e.g. `http://127.0.0.1:8080` or `socks5://127.0.0.1:1080`.
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.options.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Connects to `addr` instead of resolving `host`.
+    /// A port of 0 keeps the scheme's default one, other ports apply to plain HTTP and TLS probes.
+    pub fn resolve(mut self, host: impl Into<String>, addr: SocketAddr) -> Self {
+        self.options.resolve.insert(host.into(), addr);
+        self
+    }
+
+    /// Trusts a DER encoded certificate in addition to the webpki roots.
+    pub fn root_certificate(mut self, certificate: Vec<u8>) -> Self {
+        self.options.root_certificates.push(certificate);
+        self
+    }
+
+    /// Completes TLS probes and HTTPS requests with invalid certificates, recording why they are invalid in `Domain::tls_error`.
+    pub fn inspect_certificates(mut self, inspect: bool) -> Self {
+        self.options.inspect_certificates = inspect;
+        self
+    }
+
+    /// Sends probes and the range download through the transport instead of the network.
+    /// The user agent, proxy, resolver overrides and trusted certificates are then up to the transport.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Builds the checker. Targets are normalised, and invalid and duplicate ones are skipped and listed in `rejected`.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, checker::Checker, domain::check_result, probe::ProbePolicy};
+    /// use std::time::Duration;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let checker = Checker::builder()
+    ///         .targets("example.com\nexample2.com")
+    ///         .cf_ips(CFIPs::parse("104.16.0.0/13\n2606:4700::/32"))
+    ///         .probe_policy(ProbePolicy { timeout: Duration::from_secs(5), ..Default::default() })
+    ///         .detectors(check_result::CF_IP | check_result::CF_TRACE)
+    ///         .concurrency(8)
+    ///         .user_agent("cfd")
+    ///         .resolve("example.com", "192.0.2.1:0".parse().unwrap())
+    ///         .build()
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(checker.reports().len(), 2);
+    ///     assert_eq!(checker.cf_ips.ipsv4, vec!["104.16.0.0/13".to_string()]);
+    /// }
+    /// ```
+    pub async fn build(self) -> Result<Checker, Box<dyn Error>> {
+        let probe = match self.transport {
+            Some(transport) => Probe::with_transport(self.policy, transport),
+            None => Probe::new(self.policy, &self.options)?,
+        };
+        let targets = Targets::parse(self.targets.iter().map(|target| target.as_str()));
+        let mut reports = vec![];
+        // Expanded targets can meet each other or ones given with a port, each URL is checked once.
+        let mut seen = HashSet::new();
+        for domain in targets.domains {
+            let domain = domain.with_detectors(self.detectors);
+            let domains = if self.ports.is_empty() || domain.explicit_port {
+                vec![domain]
+            } else {
+                self.ports
+                    .iter()
+                    .map(|port| domain.clone().with_port(*port))
+                    .collect()
+            };
+            reports.extend(
+                domains
+                    .into_iter()
+                    .filter(|domain| seen.insert(domain.url()))
+                    .map(|domain| DomainReport {
+                        domain,
+                        error: None,
+                    }),
+            );
+        }
+        let cf_ips = match self.cf_ips {
+            Some(cf_ips) => cf_ips,
+            None => Arc::new(CFIPs::load_with(probe.transport()).await?),
+        };
+        Ok(Checker {
+            reports,
+            rejected: targets.rejected,
+            cf_ips,
+            probe: Arc::new(probe),
+            concurrency: self.concurrency,
+        })
+    }
 }
 
 impl Checker {
     /// Build a new checker instance.
     /// The function takes one or several domains separated by newline characters as input
     /// #Example:
-    /// ```
+    /// ```no_run
     /// use cfd::checker::Checker;
     /// #[tokio::main]
     /// async fn main(){
@@ -28,73 +333,300 @@ impl Checker {
     /// }
     /// ```
     pub async fn build(target: String) -> Result<Self, Box<dyn Error>> {
-        let target = target.split("\n").collect::<Vec<&str>>();
-        let mut domains = vec![];
-        target.iter().for_each(|domain| {
-            if let Ok(domain) = Domain::build(domain.to_string()) {
-                domains.push(Arc::new(Mutex::new(domain)));
-            }
-        });
-        let cf_ips = CFIPs::load().await?;
-        Ok(Self {
-            domains,
-            cf_ips: Arc::new(cf_ips),
-        })
+        Self::build_with_detectors(target, check_result::DEFAULT).await
+    }
+
+    /// Build a new checker instance which runs only the given detectors.
+    /// The detectors are a bit mask of `check_result` bits.
+    /// #Example:
+    /// ```no_run
+    /// use cfd::{checker::Checker, domain::check_result};
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let detectors = check_result::DEFAULT | check_result::CF_TRACE;
+    ///     let checker = Checker::build_with_detectors("example.com".to_string(), detectors).await;
+    ///     assert!(checker.is_ok());
+    /// }
+    /// ```
+    pub async fn build_with_detectors(
+        target: String,
+        detectors: u8,
+    ) -> Result<Self, Box<dyn Error>> {
+        Checker::builder()
+            .targets(&target)
+            .detectors(detectors)
+            .build()
+            .await
+    }
+
+    /// Returns a builder to configure a checker.
+    pub fn builder() -> CheckerBuilder {
+        CheckerBuilder::default()
     }
 }
 
 impl Checker {
     /// Starts a check to determine if domains are behind CF.
+    /// Returns a summary with timings, counts per status and errors of the check.
     /// #Example:
     /// ```
-    /// use cfd::checker::Checker;
+    /// use cfd::{cf_ips::CFIPs, checker::Checker, transport::{HttpResponse, MockTransport}};
+    /// use std::sync::Arc;
     /// #[tokio::main]
     /// async fn main(){
-    ///    let target = "cloudflare.com";
-    ///    let mut checker = Checker::build(target.to_string()).await.unwrap();
-    ///    checker.check().await.unwrap();
-    ///    assert_eq!(checker.cf_detected_domains().await.len() > 0, true);
+    ///    let transport = MockTransport::new()
+    ///        .with_response("http://example.com", HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"));
+    ///    let mut checker = Checker::builder()
+    ///        .targets("example.com\nexample.org")
+    ///        .cf_ips(CFIPs::parse("104.16.0.0/13"))
+    ///        .transport(Arc::new(transport))
+    ///        .build()
+    ///        .await
+    ///        .unwrap();
+    ///    let summary = checker.check().await.unwrap();
+    ///    assert_eq!((summary.detected, summary.unreachable), (1, 1));
+    /// }
+    /// ```
+    pub async fn check(&mut self) -> Result<RunSummary, Box<dyn Error>> {
+        self.check_with_progress(None).await
+    }
+
+    /// Starts a check like `check` does, sending a progress update to the channel each time a domain has been checked.
+    /// The channel is closed once the check is done.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, checker::Checker};
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let mut checker = Checker::builder()
+    ///         .cf_ips(CFIPs::parse("104.16.0.0/13"))
+    ///         .build()
+    ///         .await
+    ///         .unwrap();
+    ///     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    ///     let summary = checker.check_with_progress(Some(tx)).await.unwrap();
+    ///     assert_eq!(summary.total(), 0);
+    ///     assert!(rx.recv().await.is_none());
     /// }
     /// ```
-    pub async fn check(&mut self) -> Result<(), Box<dyn Error>> {
+    pub async fn check_with_progress(
+        &mut self,
+        progress: Option<UnboundedSender<Progress>>,
+    ) -> Result<RunSummary, Box<dyn Error>> {
+        let start = Instant::now();
         let mut handles = vec![];
-        for domain in self.domains.iter_mut() {
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let done = Arc::new(AtomicUsize::new(0));
+        let total = self.reports.len();
+        for report in self.reports.iter() {
             let cf_ips = self.cf_ips.clone();
-            let domain = domain.clone();
+            let probe = self.probe.clone();
+            let mut domain = report.domain.clone();
+            let semaphore = semaphore.clone();
+            let done = done.clone();
+            let progress = progress.clone();
             let handle = tokio::spawn(async move {
-                domain.lock().await.verify_domain(cf_ips).await.unwrap();
+                let start = Instant::now();
+                let _permit = semaphore.acquire().await?;
+                let result = domain
+                    .verify_domain_with(cf_ips, &probe)
+                    .await
+                    .map_err(|e| e.to_string());
+                if let Some(progress) = progress {
+                    let _ = progress.send(Progress {
+                        done: done.fetch_add(1, Ordering::SeqCst) + 1,
+                        total,
+                        domain: domain.target(),
+                    });
+                }
+                Ok::<_, tokio::sync::AcquireError>((domain, start.elapsed(), result.err()))
             });
             handles.push(handle);
         }
-        for handle in handles {
-            handle.await?;
+        let mut summary = RunSummary::default();
+        for (report, handle) in self.reports.iter_mut().zip(handles) {
+            match handle.await {
+                Ok(Ok((domain, duration, error))) => {
+                    summary.durations.insert(domain.target(), duration);
+                    report.domain = domain;
+                    report.error = error;
+                }
+                Ok(Err(e)) => report.error = Some(e.to_string()),
+                Err(e) => report.error = Some(e.to_string()),
+            }
+            if let Some(error) = &report.error {
+                summary.errors.push((report.domain.target(), error.clone()));
+            }
+            if report.domain.is_unreachable {
+                summary.unreachable += 1;
+            } else if report.is_exposed() {
+                summary.exposed += 1;
+            } else if report.is_detected() {
+                summary.detected += 1;
+            } else {
+                summary.not_detected += 1;
+            }
         }
-        Ok(())
+        summary.duration = start.elapsed();
+        Ok(summary)
     }
 }
 
 impl Checker {
-    /// Returns a vector of domains that are behind CF.
+    /// Returns reports of all domains in the order they were given.
+    pub fn reports(&self) -> &[DomainReport] {
+        &self.reports
+    }
+
+    /// Returns target lines which were rejected as invalid or duplicate, with line numbers and reasons.
     /// #Example:
     /// ```
-    /// use cfd::checker::Checker;
+    /// use cfd::{cf_ips::CFIPs, checker::Checker};
     /// #[tokio::main]
     /// async fn main(){
-    ///    let target = "example.com\ncloudflare.com";
-    ///    let mut checker = Checker::build(target.to_string()).await.unwrap();
+    ///     let checker = Checker::builder()
+    ///         .targets("example.com\nEXAMPLE.com.\nexample.com/*")
+    ///         .targets("foo.*.example.com")
+    ///         .cf_ips(CFIPs::parse("104.16.0.0/13"))
+    ///         .build()
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(checker.reports().len(), 2);
+    ///     assert_eq!(checker.rejected()[0].to_string(), "line 2: EXAMPLE.com.: Duplicate of line 1");
+    ///     assert_eq!(checker.rejected()[1].line, 4);
+    /// }
+    /// ```
+    pub fn rejected(&self) -> &[Rejected] {
+        &self.rejected
+    }
+
+    /// Consumes the checker and returns reports of all domains.
+    pub fn into_reports(self) -> Vec<DomainReport> {
+        self.reports
+    }
+
+    /// Returns reports of domains that are behind CF.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, checker::Checker, transport::{HttpResponse, MockTransport}};
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///    let transport = MockTransport::new()
+    ///        .with_response("http://example.com", HttpResponse::new(200))
+    ///        .with_response("http://example.net", HttpResponse::new(200).header("server", "cloudflare"));
+    ///    let mut checker = Checker::builder()
+    ///        .targets("example.com\nexample.net")
+    ///        .cf_ips(CFIPs::parse("104.16.0.0/13"))
+    ///        .transport(Arc::new(transport))
+    ///        .build()
+    ///        .await
+    ///        .unwrap();
+    ///    checker.check().await.unwrap();
+    ///    assert_eq!(checker.detected().len() == 1, true);
+    ///    assert_eq!(checker.detected()[0].domain.name, "example.net");
+    /// }
+    /// ```
+    pub fn detected(&self) -> Vec<&DomainReport> {
+        self.filter(|report| report.is_detected())
+    }
+
+    /// Returns reports of exposed origins: domains serving a CF Origin CA certificate directly.
+    pub fn exposed(&self) -> Vec<&DomainReport> {
+        self.filter(|report| report.is_exposed())
+    }
+
+    /// Returns reports of reachable domains without CF presence.
+    pub fn not_detected(&self) -> Vec<&DomainReport> {
+        self.filter(|report| {
+            !report.domain.is_unreachable && !report.is_exposed() && !report.is_detected()
+        })
+    }
+
+    /// Returns reports of unreachable domains.
+    pub fn unreachable(&self) -> Vec<&DomainReport> {
+        self.filter(|report| report.domain.is_unreachable)
+    }
+
+    /// Returns reports of domains which checks failed.
+    pub fn errors(&self) -> Vec<&DomainReport> {
+        self.filter(|report| report.error.is_some())
+    }
+
+    /// Groups hosts by registrable domain (eTLD+1) and counts how many of them are behind CF.
+    /// Groups are in the order their first hosts were given.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, checker::Checker, transport::{HttpResponse, MockTransport}};
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///    let transport = MockTransport::new()
+    ///        .with_response("http://example.co.uk", HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"))
+    ///        .with_response("http://www.example.co.uk", HttpResponse::new(200))
+    ///        .with_response("http://api.example.co.uk/v1", HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"))
+    ///        .with_response("http://example.org", HttpResponse::new(200));
+    ///    let mut checker = Checker::builder()
+    ///        .targets("example.co.uk\nwww.example.co.uk\napi.example.co.uk/v1\nexample.org\nmail.example.co.uk")
+    ///        .cf_ips(CFIPs::parse("104.16.0.0/13"))
+    ///        .transport(Arc::new(transport))
+    ///        .build()
+    ///        .await
+    ///        .unwrap();
     ///    checker.check().await.unwrap();
-    ///    assert_eq!(checker.cf_detected_domains().await.len() == 1, true);
+    ///    let coverage = checker.apex_coverage();
+    ///    assert_eq!(coverage[0].to_string(), "2 of 4 hosts of example.co.uk are behind CF");
+    ///    assert_eq!((coverage[0].not_detected(), coverage[0].unreachable), (1, 1));
+    ///    assert_eq!(coverage[1].apex, "example.org");
     /// }
     /// ```
-    pub async fn cf_detected_domains(&self) -> Vec<Arc<Mutex<Domain>>> {
-        let mut v = vec![];
-        let iter = self.domains.iter();
-        for domain in iter {
-            let domain = domain.clone();
-            if domain.lock().await.check_result != 0 {
-                v.push(domain.clone());
+    #[cfg(feature = "public-suffix")]
+    pub fn apex_coverage(&self) -> Vec<ApexCoverage> {
+        // Reports per host, in the order hosts were given.
+        let mut hosts: Vec<Vec<&DomainReport>> = vec![];
+        let mut host_index: HashMap<&str, usize> = HashMap::new();
+        for report in self.reports.iter() {
+            let index = *host_index.entry(&report.domain.name).or_insert_with(|| {
+                hosts.push(vec![]);
+                hosts.len() - 1
+            });
+            hosts[index].push(report);
+        }
+        let mut coverage: Vec<ApexCoverage> = vec![];
+        let mut apex_index: HashMap<String, usize> = HashMap::new();
+        for reports in hosts {
+            let domain = &reports[0].domain;
+            let apex = match domain.apex() {
+                Some(apex) => idna::domain_to_unicode(&apex).0,
+                None => domain.display_name(),
+            };
+            let index = *apex_index.entry(apex.clone()).or_insert_with(|| {
+                coverage.push(ApexCoverage {
+                    apex,
+                    hosts: 0,
+                    detected: 0,
+                    exposed: 0,
+                    unreachable: 0,
+                });
+                coverage.len() - 1
+            });
+            let group = &mut coverage[index];
+            group.hosts += 1;
+            if reports.iter().any(|report| report.is_exposed()) {
+                group.exposed += 1;
+            } else if reports.iter().any(|report| report.is_detected()) {
+                group.detected += 1;
+            } else if reports.iter().all(|report| report.domain.is_unreachable) {
+                group.unreachable += 1;
             }
         }
-        v
+        coverage
+    }
+
+    fn filter(&self, predicate: impl Fn(&DomainReport) -> bool) -> Vec<&DomainReport> {
+        self.reports
+            .iter()
+            .filter(|report| predicate(report))
+            .collect()
     }
 }
@@ -0,0 +1,254 @@
+//! CIDR range type.
+//! It parses IPv4 and IPv6 ranges and answers containment and overlap questions.
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+/// An IPv4 or IPv6 network with its host bits cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Builds a new CIDR, clearing host bits of the address.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// let cidr = Cidr::new("104.16.1.1".parse().unwrap(), 13).unwrap();
+    /// assert_eq!(cidr.to_string(), "104.16.0.0/13");
+    /// assert!(Cidr::new("104.16.0.0".parse().unwrap(), 33).is_err());
+    /// ```
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, String> {
+        let width = Cidr::width_of(&addr);
+        if prefix > width {
+            return Err(format!("Invalid prefix length: /{}", prefix));
+        }
+        let bits = Cidr::bits_of(&addr) & Cidr::mask(prefix, width);
+        Ok(Self {
+            network: Cidr::addr_from_bits(bits, width),
+            prefix,
+        })
+    }
+
+    /// Returns the network address.
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// Returns the prefix length.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Checks if the CIDR is an IPv4 one.
+    pub fn is_ipv4(&self) -> bool {
+        self.network.is_ipv4()
+    }
+
+    /// Checks if an IP address is in the CIDR.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// let cidr: Cidr = "2606:4700::/32".parse().unwrap();
+    /// assert!(cidr.contains_ip(&"2606:4700::6810:84e5".parse().unwrap()));
+    /// assert!(!cidr.contains_ip(&"104.16.0.1".parse().unwrap()));
+    /// ```
+    pub fn contains_ip(&self, ip: &IpAddr) -> bool {
+        self.contains(&Cidr::from(*ip))
+    }
+
+    /// Checks if another CIDR is entirely in this one.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// let cidr: Cidr = "104.16.0.0/13".parse().unwrap();
+    /// assert!(cidr.contains(&"104.17.0.0/16".parse().unwrap()));
+    /// assert!(!cidr.contains(&"104.16.0.0/12".parse().unwrap()));
+    /// ```
+    pub fn contains(&self, other: &Cidr) -> bool {
+        let width = Cidr::width_of(&self.network);
+        width == Cidr::width_of(&other.network)
+            && self.prefix <= other.prefix
+            && Cidr::bits_of(&other.network) & Cidr::mask(self.prefix, width)
+                == Cidr::bits_of(&self.network)
+    }
+
+    /// Checks if two CIDRs share at least one address.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// let cidr: Cidr = "104.16.0.0/13".parse().unwrap();
+    /// assert!(cidr.overlaps(&"104.0.0.0/8".parse().unwrap()));
+    /// assert!(!cidr.overlaps(&"104.24.0.0/14".parse().unwrap()));
+    /// ```
+    pub fn overlaps(&self, other: &Cidr) -> bool {
+        self.contains(other) || other.contains(self)
+    }
+
+    /// Returns the number of addresses in the CIDR.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// assert_eq!("173.245.48.0/20".parse::<cfd::cidr::Cidr>().unwrap().size(), 4096);
+    /// ```
+    pub fn size(&self) -> u128 {
+        let host_bits = u32::from(Cidr::width_of(&self.network) - self.prefix);
+        1u128.checked_shl(host_bits).unwrap_or(u128::MAX)
+    }
+
+    /// Merges adjacent and overlapping CIDRs into the smallest equivalent list.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// let cidrs = ["10.0.0.0/24", "10.0.1.0/24", "10.0.0.128/25", "2001:db8::/33", "2001:db8:8000::/33"]
+    ///     .iter()
+    ///     .map(|cidr| cidr.parse().unwrap())
+    ///     .collect::<Vec<Cidr>>();
+    /// let merged = Cidr::aggregate(&cidrs);
+    /// assert_eq!(merged, vec!["10.0.0.0/23".parse().unwrap(), "2001:db8::/32".parse().unwrap()]);
+    /// ```
+    pub fn aggregate(cidrs: &[Cidr]) -> Vec<Cidr> {
+        let mut cidrs = cidrs.to_vec();
+        // A network sorts before the networks it contains.
+        cidrs.sort();
+        let mut merged: Vec<Cidr> = vec![];
+        for cidr in cidrs {
+            if merged.last().is_some_and(|last| last.contains(&cidr)) {
+                continue;
+            }
+            merged.push(cidr);
+            while merged.len() >= 2 {
+                let (a, b) = (merged[merged.len() - 2], merged[merged.len() - 1]);
+                match a.merge(&b) {
+                    Some(parent) => {
+                        merged.truncate(merged.len() - 2);
+                        merged.push(parent);
+                    }
+                    None => break,
+                }
+            }
+        }
+        merged
+    }
+
+    /// Returns the number of distinct addresses in a list of CIDRs.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// let cidrs = vec!["10.0.0.0/24".parse().unwrap(), "10.0.0.0/25".parse().unwrap()];
+    /// assert_eq!(Cidr::count_addresses(&cidrs), 256);
+    /// ```
+    pub fn count_addresses(cidrs: &[Cidr]) -> u128 {
+        Cidr::aggregate(cidrs)
+            .iter()
+            .fold(0u128, |sum, cidr| sum.saturating_add(cidr.size()))
+    }
+
+    /// Returns the common part of two CIDRs, which is the more specific one if they overlap.
+    /// #Example:
+    /// ```
+    /// use cfd::cidr::Cidr;
+    /// let cidr: Cidr = "104.16.0.0/13".parse().unwrap();
+    /// assert_eq!(cidr.intersection(&"104.0.0.0/8".parse().unwrap()), Some(cidr));
+    /// assert_eq!(cidr.intersection(&"10.0.0.0/8".parse().unwrap()), None);
+    /// ```
+    pub fn intersection(&self, other: &Cidr) -> Option<Cidr> {
+        if self.contains(other) {
+            Some(*other)
+        } else if other.contains(self) {
+            Some(*self)
+        } else {
+            None
+        }
+    }
+
+    /// Merges two sibling CIDRs into their parent.
+    fn merge(&self, other: &Cidr) -> Option<Cidr> {
+        if self == other
+            || self.prefix != other.prefix
+            || self.prefix == 0
+            || Cidr::width_of(&self.network) != Cidr::width_of(&other.network)
+        {
+            return None;
+        }
+        let parent = Cidr::new(self.network, self.prefix - 1).ok()?;
+        parent.contains(other).then_some(parent)
+    }
+
+    fn width_of(addr: &IpAddr) -> u8 {
+        match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+
+    fn bits_of(addr: &IpAddr) -> u128 {
+        match addr {
+            IpAddr::V4(addr) => u128::from(u32::from(*addr)),
+            IpAddr::V6(addr) => u128::from(*addr),
+        }
+    }
+
+    fn addr_from_bits(bits: u128, width: u8) -> IpAddr {
+        if width == 32 {
+            IpAddr::V4(Ipv4Addr::from(bits as u32))
+        } else {
+            IpAddr::V6(Ipv6Addr::from(bits))
+        }
+    }
+
+    fn mask(prefix: u8, width: u8) -> u128 {
+        let all = if width == 32 {
+            u128::from(u32::MAX)
+        } else {
+            u128::MAX
+        };
+        let host_bits = u32::from(width - prefix);
+        all & !(1u128.checked_shl(host_bits).unwrap_or(0).wrapping_sub(1))
+    }
+}
+
+impl From<IpAddr> for Cidr {
+    fn from(addr: IpAddr) -> Self {
+        Self {
+            network: addr,
+            prefix: Cidr::width_of(&addr),
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parses a CIDR like `104.16.0.0/13`. A bare IP is parsed as a single host network.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|_| format!("Invalid IP address: {}", addr))?;
+        match prefix {
+            Some(prefix) => Cidr::new(
+                addr,
+                prefix
+                    .parse()
+                    .map_err(|_| format!("Invalid prefix length: /{}", prefix))?,
+            ),
+            None => Ok(Cidr::from(addr)),
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
@@ -1,9 +1,29 @@
 //! Domain struct and methods to work with it.
 
 use crate::cf_ips::CFIPs;
-use rustls::{Certificate, OwnedTrustAnchor, RootCertStore};
-use std::{error::Error, sync::Arc};
-use std::{io::Write, net::TcpStream};
+#[cfg(feature = "origin")]
+use crate::origin::{Fingerprint, OriginVerification};
+use crate::probe::Probe;
+#[cfg(feature = "public-suffix")]
+use crate::public_suffix;
+use reqwest::header::HeaderMap;
+#[cfg(feature = "origin")]
+use std::net::IpAddr;
+use std::{error::Error, fmt, sync::Arc};
+#[cfg(feature = "confusables")]
+use unicode_security::{RestrictionLevel, RestrictionLevelDetection};
+use url::Url;
+#[cfg(feature = "tls-check")]
+use {
+    crate::probe::ProbePolicy,
+    rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    rustls::{Certificate, OwnedTrustAnchor, RootCertStore, ServerName},
+    std::io::Write,
+    std::net::{SocketAddr, TcpStream, ToSocketAddrs},
+    std::sync::Mutex,
+    std::time::{Duration, SystemTime},
+    x509_parser::extensions::GeneralName,
+};
 
 /// A struct to represent bits of a domain checking result.
 pub mod check_result {
@@ -13,24 +33,444 @@ pub mod check_result {
     pub const CF_CACHE_STATUS_HEADER: u8 = 0b00100;
     pub const CF_SERVER: u8 = 0b01000;
     pub const CF_SSL: u8 = 0b10000;
+    pub const CF_TRACE: u8 = 0b100000;
+    /// A CF Origin CA certificate served to visitors, which means the origin is reachable bypassing CF.
+    pub const CF_ORIGIN_CA: u8 = 0b1000000;
+    /// Detectors enabled by default: SSL cert issuer, IP address and three headers.
+    pub const DEFAULT: u8 = 0b11111;
+    /// Bits which mean the domain is served by CF's edge. `CF_ORIGIN_CA` means the opposite and is left out.
+    pub const DETECTED: u8 = 0b111111;
+}
+
+/// Ports CF proxies without Spectrum. Spectrum proxies arbitrary TCP ports on top of them.
+pub mod ports {
+    /// Ports CF proxies plain HTTP on.
+    pub const CF_HTTP: [u16; 7] = [80, 8080, 8880, 2052, 2082, 2086, 2095];
+    /// Ports CF proxies HTTPS on.
+    pub const CF_HTTPS: [u16; 6] = [443, 2053, 2083, 2087, 2096, 8443];
+}
+
+/// A scheme a domain is probed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheme {
+    /// Plain HTTP. Redirects to HTTPS are followed.
+    #[default]
+    Http,
+    /// HTTPS.
+    Https,
+}
+
+impl Scheme {
+    /// Returns the scheme's default port.
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Scheme::Http => 80,
+            Scheme::Https => 443,
+        }
+    }
+
+    /// Guesses the scheme a port is served over: HTTPS for CF's HTTPS ports and plain HTTP for the rest.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Scheme;
+    /// assert_eq!(Scheme::for_port(8443), Scheme::Https);
+    /// assert_eq!(Scheme::for_port(8080), Scheme::Http);
+    /// ```
+    pub fn for_port(port: u16) -> Self {
+        if ports::CF_HTTPS.contains(&port) {
+            Scheme::Https
+        } else {
+            Scheme::Http
+        }
+    }
+
+    /// Guesses the scheme of a target without one: from its port if it has one, plain HTTP otherwise.
+    pub fn guess(port: Option<u16>) -> Self {
+        port.map(Scheme::for_port).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scheme::Http => write!(f, "http"),
+            Scheme::Https => write!(f, "https"),
+        }
+    }
+}
+
+/// A classification of the HTTP response a domain answered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResponseKind {
+    /// A regular response without CF's mitigation.
+    #[default]
+    Normal,
+    /// A JS challenge, e.g. the "I'm Under Attack" mode.
+    Challenge,
+    /// A managed (possibly interactive) challenge.
+    ManagedChallenge,
+    /// A request blocked by CF's WAF.
+    WafBlock,
+    /// A 520-527 error returned by CF when it can't reach the origin.
+    OriginError(u16),
+}
+
+impl ResponseKind {
+    /// Classifies a response by its status code, headers and body.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::ResponseKind;
+    /// use reqwest::header::{HeaderMap, HeaderValue};
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("cf-mitigated", HeaderValue::from_static("challenge"));
+    /// let body = "<script>window._cf_chl_opt={cType: 'managed'}</script>";
+    /// assert_eq!(ResponseKind::classify(403, &headers, body), ResponseKind::ManagedChallenge);
+    /// assert_eq!(ResponseKind::classify(522, &HeaderMap::new(), "cloudflare"), ResponseKind::OriginError(522));
+    /// assert_eq!(ResponseKind::classify(200, &HeaderMap::new(), "<html></html>"), ResponseKind::Normal);
+    /// ```
+    pub fn classify(status: u16, headers: &HeaderMap, body: &str) -> Self {
+        let body = body.to_lowercase();
+        let from_cf = headers.contains_key("cf-ray") || body.contains("cloudflare");
+        if (520..=527).contains(&status) && from_cf {
+            return ResponseKind::OriginError(status);
+        }
+        let is_mitigated = headers
+            .get("cf-mitigated")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("challenge"));
+        let has_challenge_markers = headers.keys().any(|key| key.as_str().starts_with("cf-chl"))
+            || [
+                "/cdn-cgi/challenge-platform/",
+                "_cf_chl_opt",
+                "cf-chl-",
+                "jschl",
+            ]
+            .iter()
+            .any(|marker| body.contains(marker));
+        if is_mitigated || (matches!(status, 403 | 429 | 503) && has_challenge_markers) {
+            if body.contains("ctype: 'managed'") || body.contains("ctype: \"managed\"") {
+                return ResponseKind::ManagedChallenge;
+            }
+            return ResponseKind::Challenge;
+        }
+        let has_block_markers = [
+            "cf-error-details",
+            "sorry, you have been blocked",
+            "error code: 1020",
+        ]
+        .iter()
+        .any(|marker| body.contains(marker));
+        if status == 403 && has_block_markers {
+            return ResponseKind::WafBlock;
+        }
+        ResponseKind::Normal
+    }
+}
+
+impl fmt::Display for ResponseKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseKind::Normal => write!(f, "normal"),
+            ResponseKind::Challenge => write!(f, "challenge"),
+            ResponseKind::ManagedChallenge => write!(f, "managed-challenge"),
+            ResponseKind::WafBlock => write!(f, "waf-block"),
+            ResponseKind::OriginError(status) => write!(f, "origin-error-{}", status),
+        }
+    }
+}
+
+/// A CF edge feature detected from the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Bot Management, detected by the `__cf_bm` cookie.
+    BotManagement,
+    /// A solved challenge, detected by the `cf_clearance` cookie.
+    ChallengeClearance,
+    /// Rate limiting, detected by the `__cfruid` cookie.
+    RateLimiting,
+    /// Load Balancing session affinity, detected by the `__cflb` cookie.
+    LoadBalancing,
+    /// Waiting Room, detected by the `__cfwaitingroom` cookie.
+    WaitingRoom,
+    /// Email address obfuscation, detected by the `email-decode` script.
+    EmailObfuscation,
+    /// Rocket Loader, detected by the `rocket-loader` script.
+    RocketLoader,
+    /// Zaraz, detected by `/cdn-cgi/zaraz` scripts.
+    Zaraz,
+    /// Web Analytics, detected by the `cloudflareinsights.com` beacon.
+    WebAnalytics,
+    /// JavaScript detections, injected into regular pages by Bot Fight Mode.
+    JsDetections,
+}
+
+impl Feature {
+    /// Detects CF edge features from `Set-Cookie` headers and an HTML body.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Feature;
+    /// use reqwest::header::{HeaderMap, HeaderValue};
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("set-cookie", HeaderValue::from_static("__cf_bm=abc; path=/; HttpOnly"));
+    /// let body = r#"<script src="/cdn-cgi/scripts/7d0fa10a/cloudflare-static/rocket-loader.min.js"></script>"#;
+    /// assert_eq!(Feature::detect(&headers, body), vec![Feature::BotManagement, Feature::RocketLoader]);
+    /// ```
+    pub fn detect(headers: &HeaderMap, body: &str) -> Vec<Feature> {
+        let cookies = headers
+            .get_all("set-cookie")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|cookie| cookie.split_once('=').map(|(name, _)| name.trim()))
+            .collect::<Vec<&str>>();
+        let body = body.to_lowercase();
+        let mut features = vec![];
+        for (cookie, feature) in [
+            ("__cf_bm", Feature::BotManagement),
+            ("cf_clearance", Feature::ChallengeClearance),
+            ("__cfruid", Feature::RateLimiting),
+            ("__cflb", Feature::LoadBalancing),
+            ("__cfwaitingroom", Feature::WaitingRoom),
+        ] {
+            if cookies.contains(&cookie) {
+                features.push(feature);
+            }
+        }
+        for (marker, feature) in [
+            ("email-decode", Feature::EmailObfuscation),
+            ("rocket-loader", Feature::RocketLoader),
+            ("/cdn-cgi/zaraz", Feature::Zaraz),
+            (
+                "static.cloudflareinsights.com/beacon",
+                Feature::WebAnalytics,
+            ),
+            ("__cf$cv$params", Feature::JsDetections),
+        ] {
+            if body.contains(marker) {
+                features.push(feature);
+            }
+        }
+        features
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feature::BotManagement => write!(f, "bot-management"),
+            Feature::ChallengeClearance => write!(f, "challenge-clearance"),
+            Feature::RateLimiting => write!(f, "rate-limiting"),
+            Feature::LoadBalancing => write!(f, "load-balancing"),
+            Feature::WaitingRoom => write!(f, "waiting-room"),
+            Feature::EmailObfuscation => write!(f, "email-obfuscation"),
+            Feature::RocketLoader => write!(f, "rocket-loader"),
+            Feature::Zaraz => write!(f, "zaraz"),
+            Feature::WebAnalytics => write!(f, "web-analytics"),
+            Feature::JsDetections => write!(f, "js-detections"),
+        }
+    }
+}
+
+/// Who issued the certificate a host serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateKind {
+    /// An edge certificate CF serves for proxied hostnames, e.g. Universal SSL or Advanced Certificate Manager.
+    CfEdge,
+    /// A CF Origin CA certificate. Only CF's edge trusts it, so one served directly belongs to an exposed origin.
+    CfOriginCa,
+    /// A certificate which has nothing to do with CF.
+    Other,
+}
+
+impl fmt::Display for CertificateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateKind::CfEdge => write!(f, "cf-edge"),
+            CertificateKind::CfOriginCa => write!(f, "cf-origin-ca"),
+            CertificateKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// What the TLS probe saw during the handshake.
+#[derive(Debug, Clone, Default)]
+pub struct TlsInfo {
+    /// Issuers of the peer certificates.
+    pub issuers: Vec<String>,
+    /// The protocol negotiated via ALPN.
+    pub alpn: Option<String>,
+    /// The DER encoded leaf certificate.
+    pub certificate: Option<Vec<u8>>,
+    /// DNS names the leaf certificate is issued for.
+    pub names: Vec<String>,
+    /// Why the peer certificates failed validation.
+    /// It's only set in inspection mode, where the handshake goes on with invalid certificates.
+    pub verification_error: Option<String>,
+}
+
+impl TlsInfo {
+    /// Checks if any of the peer certificates is issued by CF.
+    pub fn has_cf_issuer(&self) -> bool {
+        self.issuers
+            .iter()
+            .any(|issuer| issuer.to_lowercase().contains("cloudflare"))
+    }
+
+    /// Tells CF's edge certificates apart from its Origin CA ones and from the rest.
+    /// Origin CA certificates are issued by "CloudFlare Origin SSL (ECC) Certificate Authority".
+    /// Edge ones are issued by CF's own CAs or, for Universal SSL, cover a `*.cloudflaressl.com` name.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::{CertificateKind, TlsInfo};
+    /// let tls_info = TlsInfo {
+    ///     issuers: vec!["O=CloudFlare, Inc., OU=CloudFlare Origin SSL Certificate Authority, CN=CloudFlare Origin SSL Certificate Authority".to_string()],
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(tls_info.certificate_kind(), CertificateKind::CfOriginCa);
+    /// let tls_info = TlsInfo { issuers: vec!["C=US, O=Cloudflare, Inc., CN=Cloudflare Inc ECC CA-3".to_string()], ..Default::default() };
+    /// assert_eq!(tls_info.certificate_kind(), CertificateKind::CfEdge);
+    /// let tls_info = TlsInfo {
+    ///     issuers: vec!["C=US, O=Google Trust Services, CN=WE1".to_string()],
+    ///     names: vec!["sni.cloudflaressl.com".to_string(), "example.com".to_string()],
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(tls_info.certificate_kind(), CertificateKind::CfEdge);
+    /// let tls_info = TlsInfo { issuers: vec!["C=US, O=Let's Encrypt, CN=R3".to_string()], ..Default::default() };
+    /// assert_eq!(tls_info.certificate_kind(), CertificateKind::Other);
+    /// ```
+    pub fn certificate_kind(&self) -> CertificateKind {
+        let issuers: Vec<String> = self
+            .issuers
+            .iter()
+            .map(|issuer| issuer.to_lowercase())
+            .collect();
+        if issuers
+            .iter()
+            .any(|issuer| issuer.contains("cloudflare origin ssl"))
+        {
+            CertificateKind::CfOriginCa
+        } else if issuers.iter().any(|issuer| issuer.contains("cloudflare"))
+            || self.names.iter().any(|name| {
+                let name = name.to_lowercase();
+                name == "cloudflaressl.com" || name.ends_with(".cloudflaressl.com")
+            })
+        {
+            CertificateKind::CfEdge
+        } else {
+            CertificateKind::Other
+        }
+    }
+
+    /// Checks if the peer certificates passed validation.
+    pub fn is_valid(&self) -> bool {
+        self.verification_error.is_none()
+    }
+}
+
+/// How the TLS probe validates peer certificates.
+#[cfg(feature = "tls-check")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum CertValidation<'a> {
+    /// Accepts any certificate without validating it.
+    Skip,
+    /// Fails the handshake if certificates aren't trusted by the webpki roots or the given DER encoded ones.
+    Strict(&'a [Vec<u8>]),
+    /// Validates certificates like `Strict` does, but records the error and goes on with the handshake.
+    Inspect(&'a [Vec<u8>]),
+}
+
+/// A verifier that accepts any certificate, used to fingerprint origins.
+#[cfg(feature = "tls-check")]
+struct NoCertificateVerification;
+
+#[cfg(feature = "tls-check")]
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// A verifier that validates certificates, but records failures instead of failing the handshake.
+#[cfg(feature = "tls-check")]
+struct InspectingVerifier {
+    inner: WebPkiVerifier,
+    error: Mutex<Option<String>>,
+}
+
+#[cfg(feature = "tls-check")]
+impl ServerCertVerifier for InspectingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Err(e) = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        ) {
+            *self.error.lock().unwrap() = Some(e.to_string());
+        }
+        Ok(ServerCertVerified::assertion())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Domain {
-    /// A domain name.
+    /// A domain name, internationalized ones in punycode.
     pub name: String,
+    /// The scheme the domain is probed over.
+    pub scheme: Scheme,
+    /// The port the domain is probed on. The scheme's default one is used if it's not set.
+    pub port: Option<u16>,
+    /// If it's set, the target was given with a port, even the scheme's default one,
+    /// so it's checked on that port only.
+    pub explicit_port: bool,
+    /// The path, with the query if there is one, the HTTP probe fetches. The root is fetched if it's not set.
+    /// CF can route some paths through Workers and others straight to the origin, so results may differ per path.
+    pub path: Option<String>,
     /// A bit mask to represent the result of the check.
     pub check_result: u8,
     /// If the domain is unreachable, it will be set to true.
     pub is_unreachable: bool,
+    /// A bit mask of detectors to run, built from the `check_result` bits.
+    pub detectors: u8,
+    /// A classification of the HTTP response: normal, challenge, WAF block or origin error.
+    pub response_kind: ResponseKind,
+    /// CF edge features detected from the response's cookies and body.
+    pub features: Vec<Feature>,
+    /// The `alt-svc` header of the HTTP response.
+    pub alt_svc: Option<String>,
+    /// The protocol negotiated via ALPN during the TLS probe.
+    pub alpn: Option<String>,
+    /// Why the TLS probe failed or, in inspection mode, why the certificate failed validation.
+    pub tls_error: Option<String>,
 }
 
 impl Domain {
     /// Builds a new domain instance.
-    /// The function takes a domain name as input.
+    /// The function takes a domain name, a `host:port` pair or a URL as input.
+    /// Without a scheme, the port tells it: HTTPS for CF's HTTPS ports and plain HTTP for the rest.
+    /// A path and a query are kept for the HTTP probe, a fragment is dropped.
+    /// Internationalized names are converted to punycode. With the `confusables` feature, names with labels mixing scripts are rejected.
+    /// Names are lowercased and lose the trailing dot, ports lose the scheme's default one.
     /// #Example:
     /// ```
-    /// use cfd::domain::Domain;
+    /// use cfd::domain::{Domain, Scheme};
     /// #[tokio::main]
     /// async fn main(){
     ///     let target = "example.com";
@@ -39,23 +479,172 @@ impl Domain {
     ///     assert!(domain.is_ok());
     ///     let fail_domain = Domain::build(fail_target.to_string());
     ///     assert!(fail_domain.is_err());
+    ///     let domain = Domain::build("example.com:8443".to_string()).unwrap();
+    ///     assert_eq!((domain.scheme, domain.port), (Scheme::Https, Some(8443)));
+    ///     let domain = Domain::build("WWW.Example.com.:443".to_string()).unwrap();
+    ///     assert_eq!((domain.name.as_str(), domain.scheme, domain.port), ("www.example.com", Scheme::Https, None));
+    ///     assert!(domain.explicit_port);
+    ///     let domain = Domain::build("https://Example.com/app?id=1#top".to_string()).unwrap();
+    ///     assert_eq!((domain.name.as_str(), domain.scheme, domain.port), ("example.com", Scheme::Https, None));
+    ///     assert_eq!(domain.path.as_deref(), Some("/app?id=1"));
+    ///     let domain = Domain::build("example.com/login?next=https://x".to_string()).unwrap();
+    ///     assert_eq!((domain.scheme, domain.path.as_deref()), (Scheme::Http, Some("/login?next=https://x")));
+    ///     let domain = Domain::build("https://bücher.example/".to_string()).unwrap();
+    ///     assert_eq!((domain.name.as_str(), domain.display_name().as_str()), ("xn--bcher-kva.example", "bücher.example"));
+    ///     // A Cyrillic "а" among Latin letters.
+    ///     #[cfg(feature = "confusables")]
+    ///     assert!(Domain::build("p\u{430}ypal.com".to_string()).is_err());
+    ///     assert!(Domain::build("ftp://example.com".to_string()).is_err());
+    ///     assert!(Domain::build("example.com:0".to_string()).is_err());
     /// }
     /// ```
     pub fn build(name: String) -> Result<Self, String> {
-        if Self::is_valid(&name) {
-            return Ok(Self {
-                name: Domain::clear_name_from_proto(&name),
-                check_result: check_result::EMPTY,
-                is_unreachable: false,
-            });
-        } else {
+        let (scheme, url, explicit_port) = Domain::parse_target(&name)?;
+        let host = url.host_str().unwrap_or_default();
+        let host = host.strip_suffix('.').unwrap_or(host);
+        if !Self::is_valid_host(host) {
             return Err(format!("Invalid domain name: {}", name));
+        }
+        Domain::check_scripts(host)?;
+        let port = url.port();
+        let scheme = scheme.unwrap_or_else(|| Scheme::guess(port));
+        let port = port.filter(|port| *port != scheme.default_port());
+        let path = match (url.path(), url.query()) {
+            ("/", None) => None,
+            (path, None) => Some(path.to_string()),
+            (path, Some(query)) => Some(format!("{}?{}", path, query)),
         };
+        Ok(Self {
+            name: host.to_string(),
+            scheme,
+            port,
+            explicit_port,
+            path,
+            check_result: check_result::EMPTY,
+            is_unreachable: false,
+            detectors: check_result::DEFAULT,
+            response_kind: ResponseKind::Normal,
+            features: vec![],
+            alt_svc: None,
+            alpn: None,
+            tls_error: None,
+        })
+    }
+}
+
+impl Domain {
+    /// Sets the detectors to run during the check.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::{check_result, Domain};
+    /// let domain = Domain::build("example.com".to_string())
+    ///     .unwrap()
+    ///     .with_detectors(check_result::DEFAULT | check_result::CF_TRACE);
+    /// assert_eq!(domain.detectors, 0b111111);
+    /// ```
+    pub fn with_detectors(mut self, detectors: u8) -> Self {
+        self.detectors = detectors;
+        self
+    }
+
+    /// Sets the port to probe, guessing the scheme from it.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::{Domain, Scheme};
+    /// let domain = Domain::build("example.com".to_string()).unwrap().with_port(2053);
+    /// assert_eq!(domain.url(), "https://example.com:2053");
+    /// assert_eq!(domain.target(), "example.com:2053");
+    /// ```
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.scheme = Scheme::for_port(port);
+        self.port = Some(port);
+        self.explicit_port = true;
+        self
+    }
+
+    /// Returns the URL the HTTP probe fetches. The port is left out if it's the scheme's default one.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Domain;
+    /// assert_eq!(Domain::build("example.com".to_string()).unwrap().url(), "http://example.com");
+    /// assert_eq!(Domain::build("example.com:8080".to_string()).unwrap().url(), "http://example.com:8080");
+    /// assert_eq!(Domain::build("https://example.com:443".to_string()).unwrap().url(), "https://example.com");
+    /// assert_eq!(Domain::build("https://example.com/app?id=1".to_string()).unwrap().url(), "https://example.com/app?id=1");
+    /// ```
+    pub fn url(&self) -> String {
+        self.origin() + self.path.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the URL without the path, e.g. `https://example.com:8443`.
+    pub fn origin(&self) -> String {
+        match self.port {
+            Some(port) if port != self.scheme.default_port() => {
+                format!("{}://{}:{}", self.scheme, self.name, port)
+            }
+            _ => format!("{}://{}", self.scheme, self.name),
+        }
+    }
+
+    /// Returns the domain name in Unicode, as it's displayed. Punycode labels are decoded.
+    pub fn display_name(&self) -> String {
+        idna::domain_to_unicode(&self.name).0
+    }
+
+    /// Returns the registrable domain (eTLD+1) the domain belongs to, in punycode.
+    /// Names which are public suffixes themselves have none.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Domain;
+    /// let domain = Domain::build("https://shop.example.co.uk/cart".to_string()).unwrap();
+    /// assert_eq!(domain.apex().as_deref(), Some("example.co.uk"));
+    /// assert_eq!(domain.public_suffix().as_deref(), Some("co.uk"));
+    /// ```
+    #[cfg(feature = "public-suffix")]
+    pub fn apex(&self) -> Option<String> {
+        public_suffix::registrable_domain(&self.name)
+    }
+
+    /// Returns the public suffix (eTLD) of the domain, in punycode.
+    #[cfg(feature = "public-suffix")]
+    pub fn public_suffix(&self) -> Option<String> {
+        public_suffix::suffix(&self.name)
+    }
+
+    /// Returns the domain name in Unicode together with the port and the path if they are set, e.g. `example.com:8443/app`.
+    /// The scheme is prepended if it isn't the one the port tells, e.g. `https://example.com`.
+    /// Reports tell targets apart by it when one domain is checked on several schemes, ports or paths.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Domain;
+    /// assert_eq!(Domain::build("http://example.com/app".to_string()).unwrap().target(), "example.com/app");
+    /// assert_eq!(Domain::build("https://example.com/app".to_string()).unwrap().target(), "https://example.com/app");
+    /// assert_eq!(Domain::build("example.com:443".to_string()).unwrap().target(), "https://example.com");
+    /// assert_eq!(Domain::build("example.com:8080".to_string()).unwrap().target(), "example.com:8080");
+    /// assert_eq!(Domain::build("https://example.com:8443".to_string()).unwrap().target(), "example.com:8443");
+    /// ```
+    pub fn target(&self) -> String {
+        let mut target = String::new();
+        if self.scheme != Scheme::guess(self.port) {
+            target += &format!("{}://", self.scheme);
+        }
+        target += &self.display_name();
+        if let Some(port) = self.port {
+            target += &format!(":{}", port);
+        }
+        target + self.path.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the port the TLS probe connects to: the domain's port if it's probed over HTTPS, otherwise 443.
+    pub fn tls_port(&self) -> u16 {
+        match (self.scheme, self.port) {
+            (Scheme::Https, Some(port)) => port,
+            _ => Scheme::Https.default_port(),
+        }
     }
 }
 
 impl Domain {
-    /// Checks domain's basic validity.
+    /// Checks domain's basic validity. Targets are normalised as `build` does, so it accepts the same ones.
     /// #Example:
     /// ```
     /// use cfd::domain::Domain;
@@ -67,10 +656,44 @@ impl Domain {
     ///     assert_eq!(is_valid, true);
     ///     let is_not_valid = Domain::is_valid(&fail_target.to_string());
     ///     assert_ne!(is_not_valid, true);
+    ///     assert!(Domain::is_valid("example.com."));
+    ///     assert!(!Domain::is_valid("example.com.."));
     /// }
     /// ```
-    pub fn is_valid(domain: &String) -> bool {
-        let domain = Domain::clear_name_from_proto(domain);
+    pub fn is_valid(domain: &str) -> bool {
+        Domain::build(domain.to_string()).is_ok()
+    }
+
+    /// Checks that a punycode name decodes and that none of its labels mixes scripts the way confusables do.
+    fn check_scripts(host: &str) -> Result<(), String> {
+        let (unicode, result) = idna::domain_to_unicode(host);
+        if result.is_err() {
+            return Err(format!("Invalid internationalized domain name: {}", host));
+        }
+        match Domain::mixed_script_label(&unicode) {
+            Some(label) => Err(format!(
+                "Mixed-script label \"{}\" in {}, it may imitate another domain",
+                label, unicode
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Finds a label which isn't highly restrictive per UTS #39: single-script, or Latin with Han and Japanese or Korean.
+    #[cfg(feature = "confusables")]
+    fn mixed_script_label(name: &str) -> Option<&str> {
+        name.split('.').find(|label| {
+            !label.is_ascii() && !label.check_restriction_level(RestrictionLevel::HighlyRestrictive)
+        })
+    }
+
+    /// Without the `confusables` feature labels aren't checked.
+    #[cfg(not(feature = "confusables"))]
+    fn mixed_script_label(_name: &str) -> Option<&str> {
+        None
+    }
+
+    fn is_valid_host(domain: &str) -> bool {
         if domain.is_empty() {
             return false;
         }
@@ -82,10 +705,10 @@ impl Domain {
             return false;
         }
         for label in labels {
-            if label.len() > 63 {
+            if label.is_empty() || label.len() > 63 {
                 return false;
             }
-            if !label.chars().all(|c| c.is_alphanumeric() || c == '-') {
+            if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
                 return false;
             }
             if label.starts_with('-') || label.ends_with('-') {
@@ -94,8 +717,44 @@ impl Domain {
         }
         true
     }
-    /// Clears a domain name from http(s):// prefix.
-    ///     /// #Example:
+    /// Parses a target as a URL. A target without a scheme is parsed as a plain HTTP one and no scheme is returned.
+    /// Also tells if a port was given, since the parsed URL drops the scheme's default one.
+    fn parse_target(target: &str) -> Result<(Option<Scheme>, Url, bool), String> {
+        let target = target.trim();
+        let (scheme, rest) = Domain::split_scheme(target);
+        let (scheme, url) = match scheme {
+            Some(scheme) => match scheme.to_lowercase().as_str() {
+                "http" => (Some(Scheme::Http), Url::parse(target)),
+                "https" => (Some(Scheme::Https), Url::parse(target)),
+                _ => return Err(format!("Invalid proto: {}", scheme)),
+            },
+            None => (None, Url::parse(&format!("http://{}", target))),
+        };
+        let url = url.map_err(|e| format!("Invalid URL {}: {}", target, e))?;
+        if url.port() == Some(0) {
+            return Err(format!("Invalid port: {}", target));
+        }
+        let authority = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+        let explicit_port = authority
+            .rsplit('@')
+            .next()
+            .unwrap_or_default()
+            .contains(':');
+        Ok((scheme, url, explicit_port))
+    }
+
+    /// Splits a target into its scheme, if it has one, and the rest.
+    /// A `://` after the host, e.g. in a query, doesn't make a scheme.
+    pub(crate) fn split_scheme(target: &str) -> (Option<&str>, &str) {
+        match target.split_once("://") {
+            Some((scheme, rest)) if !scheme.contains(['/', '?', '#']) => (Some(scheme), rest),
+            _ => (None, target),
+        }
+    }
+
+    /// Clears a domain name from http(s):// prefix. Other schemes are an error.
+    /// `Domain::build` normalises the rest of a target too.
+    /// #Example:
     /// ```
     /// use cfd::domain::Domain;
     /// #[tokio::main]
@@ -103,30 +762,25 @@ impl Domain {
     ///     let target = vec!["http://example.com","https://example.com"];
     ///     for domain in target {
     ///        let res = Domain::clear_name_from_proto(&domain.to_string());
-    ///       assert_eq!(res, "example.com".to_string());
+    ///       assert_eq!(res, Ok("example.com".to_string()));
     ///     }
+    ///     assert!(Domain::clear_name_from_proto("ftp://example.com").is_err());
     /// }
     /// ```
-    pub fn clear_name_from_proto(domain: &String) -> String {
-        let domain = domain.trim();
-        let domain_chunks = domain.split("://").collect::<Vec<&str>>();
-        if domain_chunks.len() > 1 {
-            if domain_chunks[0] == "http" || domain_chunks[0] == "https" {
-                domain_chunks[1].to_string()
-            } else {
-                panic!("Invalid proto: {}", domain_chunks[0]);
-            }
-        } else {
-            domain.to_string()
+    pub fn clear_name_from_proto(domain: &str) -> Result<String, String> {
+        match Domain::split_scheme(domain.trim()) {
+            (Some("http" | "https"), rest) | (None, rest) => Ok(rest.to_string()),
+            (Some(scheme), _) => Err(format!("Invalid proto: {}", scheme)),
         }
     }
 }
 
 impl Domain {
     /// Checks the domain for five signs to see if it is behind CF.
+    /// If the `CF_TRACE` detector is enabled, the `/cdn-cgi/trace` endpoint is probed as well.
     /// The function takes a CFIPs (CloudFlare IPs) instance as input.
     /// #Example:
-    /// ```
+    /// ```no_run
     /// use cfd::{domain::Domain, cf_ips::CFIPs};
     /// use std::sync::Arc;
     /// #[tokio::main]
@@ -139,43 +793,190 @@ impl Domain {
     /// }
     /// ```
     pub async fn verify_domain(&mut self, cf_ips: Arc<CFIPs>) -> Result<(), Box<dyn Error>> {
+        self.verify_domain_with(cf_ips, &Probe::default()).await
+    }
+
+    /// Checks the domain like `verify_domain` does, going through the probe's transport with its policy.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, domain::{Domain, TlsInfo}, probe::{Probe, ProbePolicy}};
+    /// use cfd::transport::{HttpResponse, MockTransport};
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let transport = MockTransport::new()
+    ///         .with_response(
+    ///             "http://example.com",
+    ///             HttpResponse::new(200)
+    ///                 .header("server", "cloudflare")
+    ///                 .header("cf-ray", "7f1a2b3c4d5e6f70-AMS")
+    ///                 .header("cf-cache-status", "HIT")
+    ///                 .remote_ip("104.16.1.1".parse().unwrap()),
+    ///         )
+    ///         .with_tls("example.com", TlsInfo { issuers: vec!["CN=Cloudflare Inc ECC CA-3".to_string()], ..Default::default() });
+    ///     let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+    ///     let cf_ips = Arc::new(CFIPs::parse("104.16.0.0/13"));
+    ///     let mut domain = Domain::build("example.com".to_string()).unwrap();
+    ///     domain.verify_domain_with(cf_ips, &probe).await.unwrap();
+    ///     assert_eq!(domain.check_result, 0b11111);
+    /// }
+    /// ```
+    pub async fn verify_domain_with(
+        &mut self,
+        cf_ips: Arc<CFIPs>,
+        probe: &Probe,
+    ) -> Result<(), Box<dyn Error>> {
         let mut result = check_result::EMPTY;
-        if let Ok(resp) = reqwest::get("http://".to_string() + &self.name).await {
-            let ip = resp.remote_addr();
-            if ip.is_some() && cf_ips.check_ip_v4(ip.unwrap().ip().to_string().as_str()) {
+        let url = self.url();
+        let mut resp = probe.transport().get(&url).await;
+        for _ in 0..probe.policy.retries {
+            if resp.is_ok() {
+                break;
+            }
+            resp = probe.transport().get(&url).await;
+        }
+        let mut answered = resp.is_ok();
+        if let Ok(resp) = resp {
+            if resp
+                .remote_addr
+                .is_some_and(|addr| cf_ips.check_ip(&addr.ip()))
+            {
                 result |= check_result::CF_IP;
             }
-            if resp.headers().get("cf-ray").is_some() {
+            if resp.headers.get("cf-ray").is_some() {
                 result |= check_result::CF_RAY_HEADER;
             }
-            if resp.headers().get("cf-cache-status").is_some() {
+            if resp.headers.get("cf-cache-status").is_some() {
                 result |= check_result::CF_CACHE_STATUS_HEADER;
             }
             if resp
-                .headers()
+                .headers
                 .get("server")
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .contains("cloudflare")
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("cloudflare"))
             {
                 result |= check_result::CF_SERVER;
             }
-            if self.get_certificate_info().await.is_ok() {
-                result |= check_result::CF_SSL;
+            self.alt_svc = resp
+                .headers
+                .get("alt-svc")
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string());
+            self.response_kind = ResponseKind::classify(resp.status, &resp.headers, &resp.body);
+            self.features = Feature::detect(&resp.headers, &resp.body);
+            if self.detectors & check_result::CF_TRACE != 0
+                && self.has_cf_trace_endpoint(probe).await
+            {
+                result |= check_result::CF_TRACE;
+            }
+        }
+        // It runs even if the page didn't load: exposed origins often serve HTTPS only.
+        if self.detectors & (check_result::CF_SSL | check_result::CF_ORIGIN_CA) != 0 {
+            match probe
+                .transport()
+                .tls_handshake(&self.name, self.tls_port())
+                .await
+            {
+                Ok(tls_info) => {
+                    answered = true;
+                    match tls_info.certificate_kind() {
+                        // CF's edge certificates are publicly trusted, invalid ones don't count.
+                        CertificateKind::CfEdge if tls_info.is_valid() => {
+                            result |= check_result::CF_SSL
+                        }
+                        // Origin CA ones are never publicly trusted, they are seen in inspection mode
+                        // or when the Origin CA root is trusted explicitly.
+                        CertificateKind::CfOriginCa => result |= check_result::CF_ORIGIN_CA,
+                        _ => {}
+                    }
+                    self.alpn = tls_info.alpn;
+                    self.tls_error = tls_info.verification_error;
+                }
+                Err(e) => self.tls_error = Some(e.to_string()),
             }
-        } else {
-            self.is_unreachable = true
         }
-        self.check_result = result;
+        self.is_unreachable = !answered;
+        self.check_result = result & self.detectors;
         Ok(())
     }
 }
 
 impl Domain {
-    /// Gets domain's certificate info and checks if its issuer is CF.
+    /// Fetches `/cdn-cgi/trace` and checks if it is answered by CF.
+    /// Proxied hostnames answer it even when Workers strip CF's headers.
+    async fn has_cf_trace_endpoint(&self, probe: &Probe) -> bool {
+        match probe
+            .transport()
+            .get(&format!("{}/cdn-cgi/trace", self.origin()))
+            .await
+        {
+            Ok(resp) if resp.is_success() => Domain::is_cf_trace(&resp.body),
+            _ => false,
+        }
+    }
+
+    /// Checks if a body looks like CF's `/cdn-cgi/trace` output.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Domain;
+    /// let body = "fl=29f1\nh=example.com\nip=192.0.2.1\nts=1700000000.1\nvisit_scheme=http\ncolo=AMS\n";
+    /// assert!(Domain::is_cf_trace(body));
+    /// assert!(!Domain::is_cf_trace("<html>Not found</html>"));
+    /// ```
+    pub fn is_cf_trace(body: &str) -> bool {
+        let keys = body
+            .lines()
+            .filter_map(|line| line.split_once('=').map(|(key, _)| key.trim()))
+            .collect::<Vec<&str>>();
+        ["fl", "h", "colo", "visit_scheme"]
+            .iter()
+            .all(|key| keys.contains(key))
+    }
+}
+
+#[cfg(feature = "origin")]
+impl Domain {
+    /// Probes an IP directly with the domain as SNI and Host, and compares
+    /// its certificate, status code, title and body hash with the CF-fronted response.
+    /// The domain's URL and TLS port are probed, so `example.com:8443/app` is compared on 8443 and at `/app`.
     /// #Example:
+    /// ```no_run
+    /// use cfd::domain::Domain;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let domain = Domain::build("example.com".to_string()).unwrap();
+    ///     let verification = domain.verify_origin("192.0.2.1".parse().unwrap()).await.unwrap();
+    ///     println!("{}", verification.is_origin());
+    /// }
     /// ```
+    pub async fn verify_origin(&self, ip: IpAddr) -> Result<OriginVerification, Box<dyn Error>> {
+        self.verify_origin_with(ip, &Probe::default()).await
+    }
+
+    /// Probes an IP like `verify_origin` does, going through the probe's transport.
+    pub async fn verify_origin_with(
+        &self,
+        ip: IpAddr,
+        probe: &Probe,
+    ) -> Result<OriginVerification, Box<dyn Error>> {
+        let origin = Fingerprint::fetch_with(probe, self, Some(ip)).await;
+        if origin == Fingerprint::default() {
+            return Err(format!("{} doesn't answer for {}", ip, self.target()).into());
+        }
+        let proxied = Fingerprint::fetch_with(probe, self, None).await;
+        Ok(OriginVerification {
+            ip,
+            proxied,
+            origin,
+        })
+    }
+}
+
+#[cfg(feature = "tls-check")]
+impl Domain {
+    /// Gets domain's certificate info and checks if its issuer is CF.
+    /// #Example:
+    /// ```no_run
     /// use cfd::{domain::Domain, cf_ips::CFIPs};
     /// use std::sync::Arc;
     /// #[tokio::main]
@@ -188,6 +989,134 @@ impl Domain {
     /// }
     /// ```
     pub async fn get_certificate_info(&self) -> Result<bool, Box<dyn Error>> {
+        Ok(self.get_tls_info().await?.certificate_kind() == CertificateKind::CfEdge)
+    }
+
+    /// Performs a TLS handshake with the domain offering HTTP/2 and HTTP/1.1 via ALPN.
+    /// Returns the issuers of the peer certificates and the negotiated protocol.
+    /// #Example:
+    /// ```no_run
+    /// use cfd::domain::Domain;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let domain = Domain::build("cloudflare.com".to_string()).unwrap();
+    ///     let tls_info = domain.get_tls_info().await.unwrap();
+    ///     assert_eq!(tls_info.alpn, Some("h2".to_string()));
+    /// }
+    /// ```
+    pub async fn get_tls_info(&self) -> Result<TlsInfo, Box<dyn Error>> {
+        Domain::tls_handshake(
+            &self.name,
+            self.tls_port(),
+            None,
+            CertValidation::Strict(&[]),
+            ProbePolicy::default().timeout,
+        )
+    }
+
+    /// Performs a TLS handshake with the domain like `get_tls_info` does, but doesn't fail on invalid certificates.
+    /// The chain is recorded anyway, and the validation error is put into `verification_error`.
+    /// Certificates in `trusted` are trusted in addition to the webpki roots.
+    /// #Example:
+    /// ```no_run
+    /// use cfd::domain::Domain;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let domain = Domain::build("self-signed.badssl.com".to_string()).unwrap();
+    ///     let tls_info = domain.inspect_tls_info(&[]).await.unwrap();
+    ///     assert!(!tls_info.is_valid());
+    ///     assert!(!tls_info.issuers.is_empty());
+    /// }
+    /// ```
+    pub async fn inspect_tls_info(&self, trusted: &[Vec<u8>]) -> Result<TlsInfo, Box<dyn Error>> {
+        Domain::tls_handshake(
+            &self.name,
+            self.tls_port(),
+            None,
+            CertValidation::Inspect(trusted),
+            ProbePolicy::default().timeout,
+        )
+    }
+
+    /// Performs a TLS handshake using `host` as SNI on `port`, connecting to `addr` instead of `host` if it's given.
+    /// A port of 0 in `addr` stands for `port`.
+    /// Certificates are validated as `validation` says, against the webpki roots and the given DER encoded certificates.
+    /// Origins with self-signed certificates can be fingerprinted too if validation is skipped.
+    /// The `timeout` applies to the TCP connection and to each read and write.
+    pub(crate) fn tls_handshake(
+        host: &str,
+        port: u16,
+        addr: Option<SocketAddr>,
+        validation: CertValidation,
+        timeout: Duration,
+    ) -> Result<TlsInfo, Box<dyn Error>> {
+        let config = rustls::ClientConfig::builder().with_safe_defaults();
+        let mut inspector = None;
+        let mut config = match validation {
+            CertValidation::Skip => config
+                .with_custom_certificate_verifier(Arc::new(NoCertificateVerification))
+                .with_no_client_auth(),
+            CertValidation::Strict(trusted) => config
+                .with_root_certificates(Domain::root_store(trusted)?)
+                .with_no_client_auth(),
+            CertValidation::Inspect(trusted) => {
+                let verifier = Arc::new(InspectingVerifier {
+                    inner: WebPkiVerifier::new(Domain::root_store(trusted)?, None),
+                    error: Mutex::new(None),
+                });
+                inspector = Some(verifier.clone());
+                config
+                    .with_custom_certificate_verifier(verifier)
+                    .with_no_client_auth()
+            }
+        };
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let addr = match addr {
+            Some(addr) if addr.port() == 0 => SocketAddr::new(addr.ip(), port),
+            Some(addr) => addr,
+            None => (host, port)
+                .to_socket_addrs()?
+                .next()
+                .ok_or(format!("Can't resolve {}", host))?,
+        };
+        let server_name = host.try_into()?;
+        let mut conn = rustls::ClientConnection::new(Arc::new(config), server_name)?;
+        let mut sock = TcpStream::connect_timeout(&addr, timeout)?;
+        sock.set_read_timeout(Some(timeout))?;
+        sock.set_write_timeout(Some(timeout))?;
+        let mut tls = rustls::Stream::new(&mut conn, &mut sock);
+        tls.write_all(
+            format!(
+                "GET / HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nAccept-Encoding: identity\r\n\r\n",
+                host
+            )
+            .as_bytes(),
+        )?;
+        let certs = tls.conn.peer_certificates().unwrap_or_default();
+        let issuers = certs.iter().map(Domain::get_cert_issuer).collect();
+        let certificate = certs.first().map(|cert| cert.0.clone());
+        let names = certs
+            .first()
+            .map(Domain::get_cert_names)
+            .unwrap_or_default();
+        let alpn = tls
+            .conn
+            .alpn_protocol()
+            .map(|proto| String::from_utf8_lossy(proto).to_string());
+        let verification_error =
+            inspector.and_then(|verifier| verifier.error.lock().unwrap().take());
+        Ok(TlsInfo {
+            issuers,
+            alpn,
+            certificate,
+            names,
+            verification_error,
+        })
+    }
+
+    /// Builds a store of the webpki roots and the given DER encoded certificates.
+    fn root_store(trusted: &[Vec<u8>]) -> Result<RootCertStore, Box<dyn Error>> {
         let mut root_store = RootCertStore::empty();
         root_store.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
             OwnedTrustAnchor::from_subject_spki_name_constraints(
@@ -196,41 +1125,33 @@ impl Domain {
                 ta.name_constraints,
             )
         }));
-        let config = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-
-        let domain = &Domain::clear_name_from_proto(&self.name)[..];
-        let server_name = domain.try_into().unwrap();
-        let mut conn = rustls::ClientConnection::new(Arc::new(config), server_name)?;
-        let mut sock = TcpStream::connect((domain, 443))?;
-        let mut tls = rustls::Stream::new(&mut conn, &mut sock);
-        tls.write_all(
-            concat!(
-                "GET / HTTP/1.1\r\n",
-                "Host: google.com\r\n",
-                "Connection: close\r\n",
-                "Accept-Encoding: identity\r\n",
-                "\r\n"
-            )
-            .as_bytes(),
-        )?;
-        let certs = tls.conn.peer_certificates().unwrap();
-        for cert in certs {
-            if Domain::get_cert_issuer(cert)
-                .to_lowercase()
-                .contains("cloudflare")
-            {
-                return Ok(true);
-            }
+        for certificate in trusted {
+            root_store.add(&Certificate(certificate.clone()))?;
         }
-        Ok(false)
+        Ok(root_store)
+    }
+
+    fn get_cert_names(cert: &Certificate) -> Vec<String> {
+        let Ok((_, cert)) = x509_parser::parse_x509_certificate(cert.as_ref()) else {
+            return vec![];
+        };
+        let Ok(Some(san)) = cert.subject_alternative_name() else {
+            return vec![];
+        };
+        san.value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect()
     }
 
     fn get_cert_issuer(cert: &Certificate) -> String {
-        let cert = x509_parser::parse_x509_certificate(cert.as_ref()).unwrap();
-        cert.1.issuer.to_string()
+        x509_parser::parse_x509_certificate(cert.as_ref())
+            .map(|(_, cert)| cert.issuer.to_string())
+            .unwrap_or_default()
     }
 }
 
@@ -238,16 +1159,10 @@ impl Domain {
     /// Checks if domain has CF's SSL.
     /// #Example:
     /// ```
-    /// use cfd::{domain::Domain, cf_ips::CFIPs};
-    /// use std::sync::Arc;
-    /// #[tokio::main]
-    /// async fn main(){
-    ///     let target = "http://cloudflare.com".to_string();
-    ///     let cf_ips = Arc::new(CFIPs::load().await.unwrap());
-    ///     let mut domain = Domain::build(target).unwrap();
-    ///     domain.verify_domain(cf_ips).await.unwrap();
-    ///     assert_eq!(domain.has_cf_ssl(), true);
-    /// }
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.check_result = check_result::CF_SSL;
+    /// assert_eq!(domain.has_cf_ssl(), true);
     /// ```
     pub fn has_cf_ssl(&self) -> bool {
         self.check_result & check_result::CF_SSL != 0
@@ -255,16 +1170,10 @@ impl Domain {
     /// Checks if domain has CF's IP.
     /// #Example:
     /// ```
-    /// use cfd::{domain::Domain, cf_ips::CFIPs};
-    /// use std::sync::Arc;
-    /// #[tokio::main]
-    /// async fn main(){
-    ///     let target = "http://cloudflare.com".to_string();
-    ///     let cf_ips = Arc::new(CFIPs::load().await.unwrap());
-    ///     let mut domain = Domain::build(target).unwrap();
-    ///     domain.verify_domain(cf_ips).await.unwrap();
-    ///     assert_eq!(domain.has_cf_ip(), true);
-    /// }
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.check_result = check_result::CF_IP;
+    /// assert_eq!(domain.has_cf_ip(), true);
     /// ```
     pub fn has_cf_ip(&self) -> bool {
         self.check_result & check_result::CF_IP != 0
@@ -272,16 +1181,10 @@ impl Domain {
     /// Checks if domain has CF-Ray header.
     /// #Example:
     /// ```
-    /// use cfd::{domain::Domain, cf_ips::CFIPs};
-    /// use std::sync::Arc;
-    /// #[tokio::main]
-    /// async fn main(){
-    ///     let target = "http://cloudflare.com".to_string();
-    ///     let cf_ips = Arc::new(CFIPs::load().await.unwrap());
-    ///     let mut domain = Domain::build(target).unwrap();
-    ///     domain.verify_domain(cf_ips).await.unwrap();
-    ///     assert_eq!(domain.has_cf_ray_header(), true);
-    /// }
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.check_result = check_result::CF_RAY_HEADER;
+    /// assert_eq!(domain.has_cf_ray_header(), true);
     /// ```
     pub fn has_cf_ray_header(&self) -> bool {
         self.check_result & check_result::CF_RAY_HEADER != 0
@@ -289,16 +1192,10 @@ impl Domain {
     /// Checks if domain has CF-Cache-Status header.
     /// #Example:
     /// ```
-    /// use cfd::{domain::Domain, cf_ips::CFIPs};
-    /// use std::sync::Arc;
-    /// #[tokio::main]
-    /// async fn main(){
-    ///     let target = "http://cloudflare.com".to_string();
-    ///     let cf_ips = Arc::new(CFIPs::load().await.unwrap());
-    ///     let mut domain = Domain::build(target).unwrap();
-    ///     domain.verify_domain(cf_ips).await.unwrap();
-    ///     assert_eq!(domain.has_cf_cache_status_header(), true);
-    /// }
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.check_result = check_result::CF_CACHE_STATUS_HEADER;
+    /// assert_eq!(domain.has_cf_cache_status_header(), true);
     /// ```
     pub fn has_cf_cache_status_header(&self) -> bool {
         self.check_result & check_result::CF_CACHE_STATUS_HEADER != 0
@@ -306,42 +1203,108 @@ impl Domain {
     /// Checks if domain has cloudflare server header.
     /// #Example:
     /// ```
-    /// use cfd::{domain::Domain, cf_ips::CFIPs};
-    /// use std::sync::Arc;
-    /// #[tokio::main]
-    /// async fn main(){
-    ///     let target = "http://cloudflare.com".to_string();
-    ///     let cf_ips = Arc::new(CFIPs::load().await.unwrap());
-    ///     let mut domain = Domain::build(target).unwrap();
-    ///     domain.verify_domain(cf_ips).await.unwrap();
-    ///     assert_eq!(domain.has_cf_server_header(), true);
-    /// }
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.check_result = check_result::CF_SERVER;
+    /// assert_eq!(domain.has_cf_server_header(), true);
     /// ```
     pub fn has_cf_server_header(&self) -> bool {
         self.check_result & check_result::CF_SERVER != 0
     }
+    /// Checks if domain answers CF's `/cdn-cgi/trace` endpoint.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.check_result = check_result::CF_TRACE;
+    /// assert_eq!(domain.has_cf_trace(), true);
+    /// ```
+    pub fn has_cf_trace(&self) -> bool {
+        self.check_result & check_result::CF_TRACE != 0
+    }
+    /// Checks if domain serves a CF Origin CA certificate directly, i.e. it's an exposed origin.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.check_result = check_result::CF_ORIGIN_CA;
+    /// assert_eq!(domain.has_cf_origin_ca(), true);
+    /// ```
+    pub fn has_cf_origin_ca(&self) -> bool {
+        self.check_result & check_result::CF_ORIGIN_CA != 0
+    }
+}
+
+impl Domain {
+    /// Checks if domain negotiated HTTP/2 via ALPN.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Domain;
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.alpn = Some("h2".to_string());
+    /// assert_eq!(domain.has_http2(), true);
+    /// ```
+    pub fn has_http2(&self) -> bool {
+        self.alpn.as_deref() == Some("h2")
+    }
+    /// Checks if domain advertises HTTP/3 in its `alt-svc` header.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::Domain;
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// domain.alt_svc = Some("h3=\":443\"; ma=86400".to_string());
+    /// assert_eq!(domain.has_http3(), true);
+    /// ```
+    pub fn has_http3(&self) -> bool {
+        self.alt_svc.as_deref().is_some_and(|alt_svc| {
+            alt_svc
+                .split(',')
+                .any(|service| service.trim().starts_with("h3"))
+        })
+    }
+}
+
+impl Domain {
+    /// Returns detected CF edge features joined by a comma.
+    /// #Example:
+    /// ```
+    /// use cfd::domain::{Domain, Feature};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// assert_eq!(domain.get_features(), "");
+    /// domain.features = vec![Feature::RocketLoader, Feature::Zaraz];
+    /// assert_eq!(domain.get_features(), "rocket-loader, zaraz");
+    /// ```
+    pub fn get_features(&self) -> String {
+        self.features
+            .iter()
+            .map(|feature| feature.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
 }
 
 impl Domain {
     /// Returns domain status.
+    /// A domain serving a CF Origin CA certificate directly is an exposed origin, whatever else is detected.
     /// #Example:
     /// ```
-    /// use cfd::{domain::Domain, cf_ips::CFIPs};
-    /// use std::sync::Arc;
-    /// #[tokio::main]
-    /// async fn main(){
-    ///     let target = "http://cloudflare.com".to_string();
-    ///     let cf_ips = Arc::new(CFIPs::load().await.unwrap());
-    ///     let mut domain = Domain::build(target).unwrap();
-    ///     domain.verify_domain(cf_ips).await.unwrap();
-    ///     assert_eq!(domain.get_status(), "CF detected");
-    /// }
+    /// use cfd::domain::{check_result, Domain};
+    /// let mut domain = Domain::build("example.com".to_string()).unwrap();
+    /// assert_eq!(domain.get_status(), "CF not detected");
+    /// domain.check_result = check_result::CF_IP;
+    /// assert_eq!(domain.get_status(), "CF detected");
+    /// domain.check_result = check_result::CF_ORIGIN_CA;
+    /// assert_eq!(domain.get_status(), "Exposed origin");
+    /// domain.is_unreachable = true;
+    /// assert_eq!(domain.get_status(), "Unreachable");
     /// ```
     pub fn get_status(&self) -> &str {
         let status;
         if self.is_unreachable {
             status = "Unreachable";
-        } else if self.check_result != 0 {
+        } else if self.has_cf_origin_ca() {
+            status = "Exposed origin";
+        } else if self.check_result & check_result::DETECTED != 0 {
             status = "CF detected";
         } else {
             status = "CF not detected";
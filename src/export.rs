@@ -0,0 +1,252 @@
+//! Origin firewall allowlist renderer.
+//! It renders CF's IP ranges as rules for firewalls, web servers, proxies and cloud providers.
+
+use crate::cf_ips::CFIPs;
+use crate::cidr::Cidr;
+use serde_json::json;
+use std::{fmt, str::FromStr};
+
+/// Ports which CF proxies HTTP and HTTPS traffic to by default.
+const PORTS: [u16; 2] = [80, 443];
+
+/// A format to render CF's IP ranges in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// CIDRs one per line, as CF lists them. Suits `cfd ranges diff` snapshots.
+    Plain,
+    /// `iptables` and `ip6tables` commands.
+    Iptables,
+    /// An `nftables` table with interval sets.
+    Nftables,
+    /// `ufw allow` commands.
+    Ufw,
+    /// nginx `set_real_ip_from` directives.
+    Nginx,
+    /// Apache `RemoteIPTrustedProxy` directives.
+    Apache,
+    /// An HAProxy ACL.
+    Haproxy,
+    /// AWS security group ingress permissions as JSON.
+    Aws,
+    /// Terraform locals.
+    Terraform,
+}
+
+impl ExportFormat {
+    /// All supported formats.
+    pub const ALL: [ExportFormat; 9] = [
+        ExportFormat::Plain,
+        ExportFormat::Iptables,
+        ExportFormat::Nftables,
+        ExportFormat::Ufw,
+        ExportFormat::Nginx,
+        ExportFormat::Apache,
+        ExportFormat::Haproxy,
+        ExportFormat::Aws,
+        ExportFormat::Terraform,
+    ];
+
+    /// Renders CF's IP ranges in the format.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, export::ExportFormat};
+    /// let cf_ips = CFIPs {
+    ///     ipsv4: vec!["173.245.48.0/20".to_string()],
+    ///     ipsv6: vec!["2400:cb00::/32".to_string()],
+    /// };
+    /// let rules = ExportFormat::Nginx.render(&cf_ips);
+    /// assert_eq!(
+    ///     rules,
+    ///     "set_real_ip_from 173.245.48.0/20;\nset_real_ip_from 2400:cb00::/32;\nreal_ip_header CF-Connecting-IP;\n"
+    /// );
+    /// ```
+    /// Firewall formats accept CF's ranges on ports 80 and 443 and drop everyone else there:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, export::ExportFormat};
+    /// let cf_ips = CFIPs {
+    ///     ipsv4: vec!["173.245.48.0/20".to_string()],
+    ///     ipsv6: vec!["2400:cb00::/32".to_string()],
+    /// };
+    /// assert_eq!(
+    ///     ExportFormat::Iptables.render(&cf_ips),
+    ///     "iptables -A INPUT -p tcp -m multiport --dports 80,443 -s 173.245.48.0/20 -j ACCEPT\n\
+    ///      iptables -A INPUT -p tcp -m multiport --dports 80,443 -j DROP\n\
+    ///      ip6tables -A INPUT -p tcp -m multiport --dports 80,443 -s 2400:cb00::/32 -j ACCEPT\n\
+    ///      ip6tables -A INPUT -p tcp -m multiport --dports 80,443 -j DROP\n"
+    /// );
+    /// let cf_ips = CFIPs { ipsv4: vec!["173.245.48.0/20".to_string()], ipsv6: vec![] };
+    /// assert_eq!(
+    ///     ExportFormat::Nftables.render(&cf_ips),
+    ///     "table inet cloudflare {
+    ///     set cloudflare_v4 {
+    ///         type ipv4_addr
+    ///         flags interval
+    ///         elements = { 173.245.48.0/20 }
+    ///     }
+    ///     chain input {
+    ///         type filter hook input priority 0; policy accept;
+    ///         tcp dport { 80, 443 } ip saddr @cloudflare_v4 accept
+    ///         tcp dport { 80, 443 } drop
+    ///     }
+    /// }
+    /// "
+    /// );
+    /// ```
+    pub fn render(&self, cf_ips: &CFIPs) -> String {
+        let (ipsv4, ipsv6): (Vec<Cidr>, Vec<Cidr>) =
+            cf_ips.ranges().into_iter().partition(|cidr| cidr.is_ipv4());
+        let all = || ipsv4.iter().chain(ipsv6.iter());
+        let ports = PORTS.map(|port| port.to_string()).join(",");
+        let mut out = String::new();
+        match self {
+            ExportFormat::Plain => {
+                for cidr in all() {
+                    out += &format!("{}\n", cidr);
+                }
+            }
+            ExportFormat::Iptables => {
+                for (command, ranges) in [("iptables", &ipsv4), ("ip6tables", &ipsv6)] {
+                    for cidr in ranges {
+                        out += &format!(
+                            "{} -A INPUT -p tcp -m multiport --dports {} -s {} -j ACCEPT\n",
+                            command, ports, cidr
+                        );
+                    }
+                    // Everyone else is dropped, whatever the chain's policy is.
+                    out += &format!(
+                        "{} -A INPUT -p tcp -m multiport --dports {} -j DROP\n",
+                        command, ports
+                    );
+                }
+            }
+            ExportFormat::Nftables => {
+                let ports = PORTS.map(|port| port.to_string()).join(", ");
+                // nft rejects empty element lists, so families without ranges get neither a set nor a rule.
+                let families = [
+                    ("v4", "ipv4_addr", "ip", &ipsv4),
+                    ("v6", "ipv6_addr", "ip6", &ipsv6),
+                ]
+                .into_iter()
+                .filter(|(_, _, _, ranges)| !ranges.is_empty())
+                .collect::<Vec<_>>();
+                out += "table inet cloudflare {\n";
+                for (name, kind, _, ranges) in families.iter() {
+                    out += &format!(
+                        "    set cloudflare_{} {{\n        type {}\n        flags interval\n        elements = {{ {} }}\n    }}\n",
+                        name,
+                        kind,
+                        ExportFormat::join(ranges, ", ")
+                    );
+                }
+                out += "    chain input {\n        type filter hook input priority 0; policy accept;\n";
+                for (name, _, family, _) in families.iter() {
+                    out += &format!(
+                        "        tcp dport {{ {} }} {} saddr @cloudflare_{} accept\n",
+                        ports, family, name
+                    );
+                }
+                out += &format!("        tcp dport {{ {} }} drop\n", ports);
+                out += "    }\n}\n";
+            }
+            ExportFormat::Ufw => {
+                for cidr in all() {
+                    out += &format!("ufw allow proto tcp from {} to any port {}\n", cidr, ports);
+                }
+            }
+            ExportFormat::Nginx => {
+                for cidr in all() {
+                    out += &format!("set_real_ip_from {};\n", cidr);
+                }
+                out += "real_ip_header CF-Connecting-IP;\n";
+            }
+            ExportFormat::Apache => {
+                out += "RemoteIPHeader CF-Connecting-IP\n";
+                for cidr in all() {
+                    out += &format!("RemoteIPTrustedProxy {}\n", cidr);
+                }
+            }
+            ExportFormat::Haproxy => {
+                for cidr in all() {
+                    out += &format!("acl from_cloudflare src {}\n", cidr);
+                }
+            }
+            ExportFormat::Aws => {
+                let permissions = PORTS
+                    .iter()
+                    .map(|port| {
+                        json!({
+                            "IpProtocol": "tcp",
+                            "FromPort": port,
+                            "ToPort": port,
+                            "IpRanges": ipsv4
+                                .iter()
+                                .map(|cidr| json!({ "CidrIp": cidr.to_string(), "Description": "Cloudflare" }))
+                                .collect::<Vec<_>>(),
+                            "Ipv6Ranges": ipsv6
+                                .iter()
+                                .map(|cidr| json!({ "CidrIpv6": cidr.to_string(), "Description": "Cloudflare" }))
+                                .collect::<Vec<_>>(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                out += &serde_json::to_string_pretty(&permissions).unwrap_or_default();
+                out += "\n";
+            }
+            ExportFormat::Terraform => {
+                out += "locals {\n";
+                for (name, ranges) in [("ipv4", &ipsv4), ("ipv6", &ipsv6)] {
+                    out += &format!("  cloudflare_{}_cidrs = [\n", name);
+                    for cidr in ranges {
+                        out += &format!("    \"{}\",\n", cidr);
+                    }
+                    out += "  ]\n";
+                }
+                out += "}\n";
+            }
+        }
+        out
+    }
+
+    fn join(ranges: &[Cidr], separator: &str) -> String {
+        ranges
+            .iter()
+            .map(|cidr| cidr.to_string())
+            .collect::<Vec<String>>()
+            .join(separator)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ExportFormat::ALL
+            .into_iter()
+            .find(|format| format.to_string() == s.to_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "Unknown format: {}. Expected one of: {}",
+                    s,
+                    ExportFormat::ALL
+                        .map(|format| format.to_string())
+                        .join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Plain => write!(f, "plain"),
+            ExportFormat::Iptables => write!(f, "iptables"),
+            ExportFormat::Nftables => write!(f, "nftables"),
+            ExportFormat::Ufw => write!(f, "ufw"),
+            ExportFormat::Nginx => write!(f, "nginx"),
+            ExportFormat::Apache => write!(f, "apache"),
+            ExportFormat::Haproxy => write!(f, "haproxy"),
+            ExportFormat::Aws => write!(f, "aws"),
+            ExportFormat::Terraform => write!(f, "terraform"),
+        }
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(feature = "origin")]
+use sha2::{Digest, Sha256};
+
 /// Converts string to vec of strings, splitting on the given delimiter.
 /// # Example
 ///
@@ -43,3 +46,38 @@ pub fn bool_to_str<'a>(b: bool) -> &'a str {
         "false"
     }
 }
+
+/// Extracts the contents of the `<title>` tag from an HTML document.
+/// # Example
+///
+/// ```
+/// use cfd::helpers::extract_title;
+/// let title = extract_title("<html><head><TITLE> Example Domain </TITLE></head></html>");
+/// assert_eq!(title, Some("Example Domain".to_string()));
+/// assert_eq!(extract_title("<html></html>"), None);
+/// ```
+///
+pub fn extract_title(html: &str) -> Option<String> {
+    let lowercase = html.to_ascii_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start + lowercase[start..].find("</title>")?;
+    Some(html[start..end].trim().to_string())
+}
+
+/// Computes a hex encoded SHA-256 digest of the given bytes.
+/// # Example
+///
+/// ```
+/// use cfd::helpers::sha256_hex;
+/// let hash = sha256_hex(b"hello");
+/// assert_eq!(hash, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+/// ```
+///
+#[cfg(feature = "origin")]
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
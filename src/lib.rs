@@ -1,33 +1,57 @@
-use std::time::Instant;
-
-use checker::Checker;
+use checker::{Checker, RunSummary};
+use domain::check_result;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod cf_ips;
 pub mod checker;
+pub mod cidr;
 pub mod domain;
+pub mod export;
 pub mod helpers;
+pub mod logs;
+#[cfg(feature = "origin")]
+pub mod origin;
+pub mod probe;
+#[cfg(feature = "public-suffix")]
+pub mod public_suffix;
+pub mod targets;
+pub mod transport;
 
 /// Runs the checker.
+/// Returns the checker together with a summary of the check. Nothing is printed.
 /// #Example:
-/// ```
+/// ```no_run
 /// use cfd::run;
 /// #[tokio::main]
 /// async fn main(){
 ///   let target = "example.com\ncloudflare.com";
-///   let checker = run(target.to_string()).await.unwrap();
-///   assert_eq!(checker.cf_detected_domains().await.len() == 1, true);
+///   let (checker, summary) = run(target.to_string()).await.unwrap();
+///   assert_eq!(checker.detected().len() == 1, true);
+///   assert_eq!(summary.total(), 2);
+/// }
+/// ```
+pub async fn run(target: String) -> Result<(Checker, RunSummary), Box<dyn std::error::Error>> {
+    run_with_detectors(target, check_result::DEFAULT).await
+}
+
+/// Runs the checker with the given detectors, a bit mask of `check_result` bits.
+/// #Example:
+/// ```no_run
+/// use cfd::{domain::check_result, run_with_detectors};
+/// #[tokio::main]
+/// async fn main(){
+///   let target = "cloudflare.com";
+///   let detectors = check_result::DEFAULT | check_result::CF_TRACE;
+///   let (checker, _) = run_with_detectors(target.to_string(), detectors).await.unwrap();
+///   assert_eq!(checker.detected().len() == 1, true);
 /// }
 /// ```
-pub async fn run(target: String) -> Result<Checker, Box<dyn std::error::Error>> {
-    let start = Instant::now();
-    let mut checker = checker::Checker::build(String::from(target.as_str())).await?;
-    checker.check().await?;
-    let end = Instant::now();
-    let duration = end - start;
-    println!(
-        "Finished in {:.2?} for {} domain(s)",
-        duration,
-        checker.domains.len()
-    );
-    Ok(checker)
+pub async fn run_with_detectors(
+    target: String,
+    detectors: u8,
+) -> Result<(Checker, RunSummary), Box<dyn std::error::Error>> {
+    let mut checker = checker::Checker::build_with_detectors(target, detectors).await?;
+    let summary = checker.check().await?;
+    Ok((checker, summary))
 }
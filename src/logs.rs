@@ -0,0 +1,127 @@
+//! Access log scanner.
+//! It extracts remote addresses from access logs and tells traffic which came through CF apart from direct one.
+
+use crate::cf_ips::CFIPs;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+/// JSON keys which commonly hold the remote address.
+const JSON_ADDR_KEYS: &[&str] = &[
+    "remote_addr",
+    "remote_ip",
+    "remoteAddr",
+    "remoteIp",
+    "client_ip",
+    "clientIp",
+    "clientip",
+    "client",
+    "ip",
+];
+
+/// A breakdown of access log lines by where they came from.
+#[derive(Debug, Clone, Default)]
+pub struct LogStats {
+    /// The number of lines with a remote address.
+    pub total: usize,
+    /// The number of requests which came from CF's ranges.
+    pub via_cf: usize,
+    /// Requests which hit the origin directly, per remote address.
+    pub direct: HashMap<IpAddr, usize>,
+    /// The number of lines without a recognisable remote address.
+    pub unparsed: usize,
+}
+
+impl LogStats {
+    /// Accounts a log line.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, logs::LogStats};
+    /// let cf_ips = CFIPs { ipsv4: vec!["172.64.0.0/13".to_string()], ipsv6: vec![] };
+    /// let mut stats = LogStats::default();
+    /// stats.add_line(r#"172.68.1.1 - - [10/Oct/2023:13:55:36 +0000] "GET / HTTP/1.1" 200 612 "-" "curl/8.0""#, &cf_ips);
+    /// stats.add_line(r#"{"remote_addr":"203.0.113.9","request":"GET / HTTP/1.1","status":200}"#, &cf_ips);
+    /// stats.add_line("garbage", &cf_ips);
+    /// assert_eq!((stats.total, stats.via_cf, stats.direct_total(), stats.unparsed), (2, 1, 1, 1));
+    /// ```
+    pub fn add_line(&mut self, line: &str, cf_ips: &CFIPs) {
+        match LogStats::extract_remote_addr(line) {
+            Some(ip) => {
+                self.total += 1;
+                if cf_ips.check_ip(&ip) {
+                    self.via_cf += 1;
+                } else {
+                    *self.direct.entry(ip).or_insert(0) += 1;
+                }
+            }
+            None if line.trim().is_empty() => {}
+            None => self.unparsed += 1,
+        }
+    }
+
+    /// Returns the number of requests which hit the origin directly.
+    pub fn direct_total(&self) -> usize {
+        self.direct.values().sum()
+    }
+
+    /// Returns the share of requests which came through CF, from 0 to 1.
+    pub fn cf_share(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.via_cf as f64 / self.total as f64
+    }
+
+    /// Returns the remote addresses which hit the origin directly most often.
+    /// #Example:
+    /// ```
+    /// use cfd::{cf_ips::CFIPs, logs::LogStats};
+    /// let cf_ips = CFIPs { ipsv4: vec![], ipsv6: vec![] };
+    /// let mut stats = LogStats::default();
+    /// for line in ["192.0.2.1 - -", "192.0.2.2 - -", "192.0.2.2 - -"] {
+    ///     stats.add_line(line, &cf_ips);
+    /// }
+    /// assert_eq!(stats.top_direct(1), vec![("192.0.2.2".parse().unwrap(), 2)]);
+    /// ```
+    pub fn top_direct(&self, count: usize) -> Vec<(IpAddr, usize)> {
+        let mut direct = self
+            .direct
+            .iter()
+            .map(|(ip, requests)| (*ip, *requests))
+            .collect::<Vec<(IpAddr, usize)>>();
+        direct.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        direct.truncate(count);
+        direct
+    }
+
+    /// Extracts the remote address from an nginx/Apache combined or a JSON access log line.
+    /// #Example:
+    /// ```
+    /// use cfd::logs::LogStats;
+    /// let ip = LogStats::extract_remote_addr(r#"{"client_ip":"[2001:db8::1]:51234"}"#);
+    /// assert_eq!(ip, Some("2001:db8::1".parse().unwrap()));
+    /// let ip = LogStats::extract_remote_addr(r#"198.51.100.7 - frank [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200 2326"#);
+    /// assert_eq!(ip, Some("198.51.100.7".parse().unwrap()));
+    /// ```
+    pub fn extract_remote_addr(line: &str) -> Option<IpAddr> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            let json = serde_json::from_str::<serde_json::Value>(line).ok()?;
+            return JSON_ADDR_KEYS
+                .iter()
+                .filter_map(|key| json.get(key).and_then(|value| value.as_str()))
+                .find_map(LogStats::parse_addr);
+        }
+        line.split_whitespace()
+            .next()
+            .and_then(LogStats::parse_addr)
+    }
+
+    /// Parses an IP which may come with a port.
+    fn parse_addr(addr: &str) -> Option<IpAddr> {
+        addr.parse::<IpAddr>()
+            .ok()
+            .or_else(|| addr.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+    }
+}
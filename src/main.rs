@@ -1,12 +1,24 @@
-use cfd::{self, checker::Checker, domain::Domain, helpers::bool_to_str};
-use clap::Parser;
+use cfd::{
+    self,
+    cf_ips::{CFIPs, Membership},
+    checker::{Checker, DomainReport, Progress, RunSummary},
+    cidr::Cidr,
+    domain::{check_result, ports, Domain},
+    export::ExportFormat,
+    helpers::{bool_to_str, sha256_hex},
+    logs::LogStats,
+    origin,
+    probe::ClientOptions,
+};
+use clap::{Parser, Subcommand};
 use prettytable::{Cell, Row, Table};
 use std::{
-    io::Write,
+    io::{BufRead, IsTerminal, Write},
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
     sync::Arc,
 };
-use tokio::sync::Mutex;
+use tokio::sync::mpsc;
 #[macro_use]
 extern crate prettytable;
 
@@ -15,91 +27,326 @@ extern crate prettytable;
 #[command(author = "Airat G. <hello@galiullin.online>")]
 #[command(version = "0.1.0")]
 #[command(about = "Checks the domain for Cloudflare presence using 5 criteria: SSL cert issuer, IP address, and three headers in the HTTP response.", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// A domain, domains divided by newline char or a file with domains.
-    target: String,
+    /// Domains can be given as host:port pairs or URLs, e.g. example.com:8443 or https://example.com:2053/app.
+    /// Paths and queries are kept and each URL is reported separately.
+    #[arg(required = true)]
+    target: Option<String>,
     /// Outputs a detailed result for each domain based on five checks.
     #[arg(short)]
     detailed: bool,
     /// Outputs only domains without Cloudflare presence.
     #[arg(short)]
     filtered: bool,
+    /// Outputs only exposed origins: domains serving a Cloudflare Origin CA certificate directly.
+    #[arg(short)]
+    exposed: bool,
+    /// Outputs a summary grouped by registrable domain (eTLD+1): how many hosts of each are behind Cloudflare.
+    #[arg(short)]
+    grouped: bool,
+    /// Also probes the /cdn-cgi/trace endpoint, which CF answers even when Workers strip its headers.
+    #[arg(short)]
+    trace: bool,
     /// The path to the folder where the cfd_report.(txt|csv) file will be stored.
     /// If a file won't be specified, output will be printed to stdout.
     /// If the detailed flag is set, the output will include checking details.
     #[arg(short)]
     output: Option<PathBuf>,
+    /// Connects to ADDR instead of resolving HOST, like curl's --resolve. ADDR is an IP or an IP with a port,
+    /// e.g. example.com:127.0.0.1:8443. The port applies to plain HTTP and TLS probes. Can be repeated.
+    #[arg(long, value_name = "HOST:ADDR", value_parser = parse_resolve)]
+    resolve: Vec<(String, SocketAddr)>,
+    /// Checks each domain on each of the given ports, e.g. 80,443,8443, and reports CF presence per port.
+    /// `cf` stands for all ports CF proxies without Spectrum. Domains given with a port are checked on it only.
+    #[arg(long, value_name = "PORTS", value_delimiter = ',', value_parser = parse_ports)]
+    ports: Vec<Vec<u16>>,
+    /// A CA bundle, PEM or DER encoded, trusted in addition to the built-in roots. Can be repeated.
+    #[arg(long, value_name = "FILE")]
+    ca_file: Vec<PathBuf>,
+    /// Doesn't fail the TLS probe and HTTPS requests on invalid certificates, but reports why they are invalid.
+    /// CF Origin CA certificates served directly, which mean exposed origins, are only seen with it
+    /// or with the Origin CA root passed via --ca-file.
+    #[arg(long)]
+    inspect_tls: bool,
 }
 
-// Добавить возможность проверки вектора айпишников для CFIPs
+#[derive(Subcommand)]
+enum Command {
+    /// Looks for origin IPs of a Cloudflare-proxied apex that bypass Cloudflare.
+    ///
+    /// Resolves subdomains from a wordlist, MX hosts, SPF and TXT records and flags IPs outside Cloudflare ranges
+    /// that serve the same TLS certificate or page title as the apex.
+    /// Fails if the apex itself doesn't resolve to Cloudflare.
+    OriginHunt {
+        /// An apex domain behind Cloudflare.
+        apex: String,
+        /// A file with subdomains to try, one per line. A built-in list is used if it won't be specified.
+        #[arg(short)]
+        wordlist: Option<PathBuf>,
+    },
+    /// Verifies that an IP serves a domain directly, bypassing Cloudflare.
+    ///
+    /// Connects to the IP with the domain as SNI and Host and compares the certificate, status code,
+    /// title and body hash with the Cloudflare-fronted response.
+    VerifyOrigin {
+        /// A domain behind Cloudflare, a host:port pair or a URL, e.g. example.com:8443 or https://example.com/app.
+        domain: String,
+        /// A candidate origin IP.
+        ip: IpAddr,
+    },
+    /// Checks IPs and CIDRs for membership in Cloudflare ranges.
+    ///
+    /// Reports for each input whether it is inside a Cloudflare range, partially overlaps
+    /// Cloudflare ranges or is outside of them, together with the matching ranges.
+    Ip {
+        /// IPs, CIDRs or files with them, one per line. Read from stdin if none are given.
+        inputs: Vec<String>,
+    },
+    /// Scans access logs to find out how much origin traffic came through Cloudflare.
+    ///
+    /// Parses nginx/Apache combined and JSON access logs, matches remote addresses against Cloudflare ranges
+    /// and lists the top sources hitting the origin directly.
+    Logs {
+        /// Access log files. Read from stdin if none are given.
+        files: Vec<PathBuf>,
+        /// The number of top direct sources to list.
+        #[arg(short = 'n', default_value_t = 10)]
+        top: usize,
+    },
+    /// Works with Cloudflare IP ranges.
+    Ranges {
+        #[command(subcommand)]
+        command: RangesCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum RangesCommand {
+    /// Renders Cloudflare IP ranges as origin firewall allowlist rules.
+    Export {
+        /// One of: plain, iptables, nftables, ufw, nginx, apache, haproxy, aws, terraform.
+        #[arg(long)]
+        format: ExportFormat,
+    },
+    /// Compares Cloudflare IP ranges with a stored snapshot and exits with 1 if they changed.
+    ///
+    /// A snapshot lists CIDRs one per line, as `cfd ranges export --format plain` prints them.
+    Diff {
+        /// A snapshot file to compare with.
+        #[arg(long)]
+        since: PathBuf,
+    },
+    /// Summarises Cloudflare IP ranges: range counts before and after merging, and address counts.
+    Summary,
+    /// Lists the parts of the given CIDRs which are in Cloudflare IP ranges.
+    Intersect {
+        /// IPs, CIDRs or files with them, one per line. Read from stdin if none are given.
+        inputs: Vec<String>,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    let mut target = cli.target;
+    if let Some(command) = cli.command {
+        return match command {
+            Command::OriginHunt { apex, wordlist } => origin_hunt(apex, wordlist).await,
+            Command::VerifyOrigin { domain, ip } => verify_origin(domain, ip).await,
+            Command::Ip { inputs } => check_ips(inputs).await,
+            Command::Logs { files, top } => scan_logs(files, top).await,
+            Command::Ranges { command } => ranges(command).await,
+        };
+    }
+    let mut target = cli.target.unwrap_or_default();
     let path = Path::new(target.as_str());
     if path.exists() && path.is_file() {
         target = std::fs::read_to_string(path)?;
     }
-    let checker = cfd::run(target).await?;
-    output(checker, cli.detailed, cli.filtered, cli.output).await?;
+    let mut detectors = check_result::DEFAULT | check_result::CF_ORIGIN_CA;
+    if cli.trace {
+        detectors |= check_result::CF_TRACE;
+    }
+    let (tx, rx) = mpsc::unbounded_channel();
+    let progress = tokio::spawn(render_progress(rx));
+    let mut builder = Checker::builder().targets(&target).detectors(detectors);
+    for (host, addr) in cli.resolve {
+        builder = builder.resolve(host, addr);
+    }
+    builder = builder.ports(&cli.ports.concat());
+    for path in cli.ca_file {
+        for certificate in ClientOptions::read_ca_file(&path)? {
+            builder = builder.root_certificate(certificate);
+        }
+    }
+    builder = builder.inspect_certificates(cli.inspect_tls);
+    let mut checker = builder.build().await?;
+    for rejected in checker.rejected() {
+        eprintln!("Skipped {}", rejected);
+    }
+    let summary = checker.check_with_progress(Some(tx)).await?;
+    progress.await?;
+    report_summary(&summary);
+    if cli.grouped {
+        output_grouped(&checker, cli.output)?;
+    } else {
+        output(
+            &checker,
+            cli.detailed,
+            cli.filtered,
+            cli.exposed,
+            cli.output,
+        )?;
+    }
+    Ok(())
+}
+
+/// Parses a `HOST:ADDR` resolver override. An IP without a port gets the port 0, which keeps the scheme's default one.
+fn parse_resolve(s: &str) -> Result<(String, SocketAddr), String> {
+    let (host, addr) = s
+        .split_once(':')
+        .ok_or_else(|| format!("Expected HOST:ADDR, got {}", s))?;
+    let addr = addr
+        .parse::<SocketAddr>()
+        .or_else(|_| addr.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 0)))
+        .map_err(|_| format!("Invalid address: {}", addr))?;
+    Ok((host.to_string(), addr))
+}
+
+/// Parses a port or `cf`, which stands for all ports CF proxies without Spectrum.
+fn parse_ports(s: &str) -> Result<Vec<u16>, String> {
+    if s.eq_ignore_ascii_case("cf") {
+        return Ok([&ports::CF_HTTP[..], &ports::CF_HTTPS[..]].concat());
+    }
+    match s.trim().parse::<u16>() {
+        Ok(port) if port != 0 => Ok(vec![port]),
+        _ => Err(format!("Invalid port: {}", s)),
+    }
+}
+
+/// Draws a progress bar on stderr until the checker is done. Nothing is drawn if stderr isn't a terminal.
+async fn render_progress(mut rx: mpsc::UnboundedReceiver<Progress>) {
+    const WIDTH: usize = 30;
+    let terminal = std::io::stderr().is_terminal();
+    let mut drawn = false;
+    while let Some(progress) = rx.recv().await {
+        if !terminal {
+            continue;
+        }
+        let filled = WIDTH * progress.done / progress.total.max(1);
+        eprint!(
+            "\r\x1b[K[{}{}] {}/{} {}",
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            progress.done,
+            progress.total,
+            progress.domain
+        );
+        drawn = true;
+    }
+    if drawn {
+        eprint!("\r\x1b[K");
+    }
+}
+
+/// Prints the duration and errors of a check to stderr, so they don't mix with the report.
+fn report_summary(summary: &RunSummary) {
+    for (domain, error) in summary.errors.iter() {
+        eprintln!("{}: {}", domain, error);
+    }
+    eprintln!(
+        "Finished in {:.2?} for {} domain(s): {} detected, {} exposed origin(s), {} not detected, {} unreachable",
+        summary.duration,
+        summary.total(),
+        summary.detected,
+        summary.exposed,
+        summary.not_detected,
+        summary.unreachable
+    );
+}
+
+/// Outputs apex coverage as a table, or as cfd_report.csv in the output folder.
+fn output_grouped(
+    checker: &Checker,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Apex",
+        "Hosts",
+        "CF detected",
+        "Exposed origin",
+        "CF not detected",
+        "Unreachable",
+        "Coverage"
+    ]);
+    for group in checker.apex_coverage() {
+        table.add_row(Row::new(vec![
+            Cell::new(group.apex.as_str()),
+            Cell::new(&group.hosts.to_string()),
+            Cell::new(&group.detected.to_string()),
+            Cell::new(&group.exposed.to_string()),
+            Cell::new(&group.not_detected().to_string()),
+            Cell::new(&group.unreachable.to_string()),
+            Cell::new(&format!("{}%", 100 * group.detected / group.hosts.max(1))),
+        ]));
+    }
+    if let Some(output) = output {
+        let path = output.with_file_name("cfd_report").with_extension("csv");
+        let mut file = std::fs::File::create(path)?;
+        table.to_csv(&mut file)?;
+    } else {
+        table.printstd();
+    }
     Ok(())
 }
 
-async fn output(
-    checker: Checker,
+fn output(
+    checker: &Checker,
     detailed: bool,
     filtered: bool,
+    exposed: bool,
     output: Option<PathBuf>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let domains;
-    if filtered {
-        domains = checker.cf_detected_domains().await;
+    let reports = if exposed {
+        checker.exposed()
+    } else if filtered {
+        checker.detected()
     } else {
-        domains = checker.domains;
-    }
-    if detailed || !detailed && !filtered {
+        checker.reports().iter().collect()
+    };
+    if detailed || !(filtered || exposed) {
         let mut table = Table::new();
         if detailed {
-            build_full_table(&mut table, domains).await?;
+            build_full_table(&mut table, &reports);
         } else {
-            build_small_table(&mut table, domains).await?;
+            build_small_table(&mut table, &reports);
         }
-        if output.is_some() {
-            let path = output
-                .unwrap()
-                .with_file_name("cfd_report")
-                .with_extension("csv");
+        if let Some(output) = output {
+            let path = output.with_file_name("cfd_report").with_extension("csv");
             let mut file = std::fs::File::create(path)?;
             table.to_csv(&mut file)?;
         } else {
             table.printstd();
         }
+    } else if let Some(output) = output {
+        let path = output.with_file_name("cfd_report").with_extension("txt");
+        let mut file = std::fs::File::create(path)?;
+        for report in reports.iter() {
+            writeln!(file, "{}", report.domain.target())?;
+        }
     } else {
-        if output.is_none() {
-            for domain in domains.iter() {
-                let domain = domain.lock().await;
-                println!("{}", domain.name);
-            }
-        } else {
-            let path = output
-                .unwrap()
-                .with_file_name("cfd_report")
-                .with_extension("txt");
-            let mut file = std::fs::File::create(path)?;
-            for domain in domains.iter() {
-                let domain = domain.lock().await;
-                writeln!(file, "{}", domain.name)?;
-            }
+        for report in reports.iter() {
+            println!("{}", report.domain.target());
         }
     }
     Ok(())
 }
 
-async fn build_full_table(
-    table: &mut Table,
-    domains: Vec<Arc<Mutex<Domain>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
+fn build_full_table(table: &mut Table, reports: &[&DomainReport]) {
     table.add_row(row![
         "Domain",
         "Unreachable",
@@ -107,34 +354,273 @@ async fn build_full_table(
         "CF IP",
         "CF-Ray",
         "CF-Cache-Status",
-        "CF-Server"
+        "CF-Server",
+        "CF-Trace",
+        "CF Origin CA",
+        "Response",
+        "Features",
+        "HTTP/2",
+        "HTTP/3",
+        "TLS Error"
     ]);
-    for domain in domains.iter() {
-        let domain = domain.lock().await;
+    for report in reports.iter() {
+        let domain = &report.domain;
         table.add_row(Row::new(vec![
-            Cell::new(domain.name.as_str()),
+            Cell::new(domain.target().as_str()),
             Cell::new(bool_to_str(domain.is_unreachable)),
             Cell::new(bool_to_str(domain.has_cf_ssl())),
             Cell::new(bool_to_str(domain.has_cf_ip())),
             Cell::new(bool_to_str(domain.has_cf_ray_header())),
             Cell::new(bool_to_str(domain.has_cf_cache_status_header())),
             Cell::new(bool_to_str(domain.has_cf_server_header())),
+            Cell::new(bool_to_str(domain.has_cf_trace())),
+            Cell::new(bool_to_str(domain.has_cf_origin_ca())),
+            Cell::new(domain.response_kind.to_string().as_str()),
+            Cell::new(domain.get_features().as_str()),
+            Cell::new(bool_to_str(domain.has_http2())),
+            Cell::new(bool_to_str(domain.has_http3())),
+            Cell::new(domain.tls_error.as_deref().unwrap_or("-")),
         ]));
     }
-    Ok(())
 }
 
-async fn build_small_table(
-    table: &mut Table,
-    domains: Vec<Arc<Mutex<Domain>>>,
-) -> Result<(), Box<dyn std::error::Error>> {
+fn build_small_table(table: &mut Table, reports: &[&DomainReport]) {
     table.add_row(row!["Domain", "Status"]);
-    for domain in domains.iter() {
-        let domain = domain.lock().await;
+    for report in reports.iter() {
+        let domain = &report.domain;
         table.add_row(Row::new(vec![
-            Cell::new(domain.name.as_str()),
+            Cell::new(domain.target().as_str()),
             Cell::new(domain.get_status()),
         ]));
     }
+}
+
+async fn origin_hunt(
+    apex: String,
+    wordlist: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let wordlist = match wordlist {
+        Some(path) => std::fs::read_to_string(path)?
+            .lines()
+            .map(|word| word.trim().to_string())
+            .filter(|word| !word.is_empty() && !word.starts_with('#'))
+            .collect(),
+        None => origin::DEFAULT_WORDLIST
+            .iter()
+            .map(|word| word.to_string())
+            .collect::<Vec<String>>(),
+    };
+    let cf_ips = Arc::new(CFIPs::load().await?);
+    let candidates = origin::hunt(&apex, &wordlist, cf_ips).await?;
+    let mut table = Table::new();
+    table.add_row(row!["IP", "Source", "Same cert", "Same title", "Exposed"]);
+    for candidate in candidates.iter() {
+        table.add_row(Row::new(vec![
+            Cell::new(candidate.ip.to_string().as_str()),
+            Cell::new(candidate.source.as_str()),
+            Cell::new(bool_to_str(candidate.same_certificate)),
+            Cell::new(bool_to_str(candidate.same_title)),
+            Cell::new(bool_to_str(candidate.is_exposed())),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+async fn verify_origin(domain: String, ip: IpAddr) -> Result<(), Box<dyn std::error::Error>> {
+    let domain = Domain::build(domain)?;
+    let verification = domain.verify_origin(ip).await?;
+    let comparison = verification.comparison();
+    let (proxied, origin) = (&verification.proxied, &verification.origin);
+    let certificate = |fingerprint: &origin::Fingerprint| {
+        fingerprint
+            .certificate
+            .as_deref()
+            .map(sha256_hex)
+            .unwrap_or_default()
+    };
+    let status = |fingerprint: &origin::Fingerprint| {
+        fingerprint
+            .status
+            .map(|status| status.to_string())
+            .unwrap_or_default()
+    };
+    let mut table = Table::new();
+    table.add_row(row!["Check", "Cloudflare", "Origin", "Match"]);
+    table.add_row(row![
+        "Certificate",
+        certificate(proxied),
+        certificate(origin),
+        bool_to_str(comparison.same_certificate)
+    ]);
+    table.add_row(row![
+        "Status",
+        status(proxied),
+        status(origin),
+        bool_to_str(comparison.same_status)
+    ]);
+    table.add_row(row![
+        "Title",
+        proxied.title.clone().unwrap_or_default(),
+        origin.title.clone().unwrap_or_default(),
+        bool_to_str(comparison.same_title)
+    ]);
+    table.add_row(row![
+        "Body hash",
+        proxied.body_hash.clone().unwrap_or_default(),
+        origin.body_hash.clone().unwrap_or_default(),
+        bool_to_str(comparison.same_body)
+    ]);
+    table.printstd();
+    println!(
+        "{} serves {}: {}",
+        ip,
+        domain.target(),
+        bool_to_str(verification.is_origin())
+    );
+    Ok(())
+}
+
+async fn check_ips(inputs: Vec<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = read_entries(inputs)?;
+    let cf_ips = CFIPs::load().await?;
+    let mut table = Table::new();
+    table.add_row(row!["Input", "Cloudflare", "Range"]);
+    for entry in entries.iter() {
+        let (status, ranges) = match entry.parse::<Cidr>() {
+            Ok(cidr) => match cf_ips.membership(&cidr) {
+                Membership::Inside(ranges) => ("true", join_ranges(&ranges)),
+                Membership::Partial(ranges) => ("partial", join_ranges(&ranges)),
+                Membership::Outside => ("false", String::new()),
+            },
+            Err(err) => ("invalid", err),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(entry.as_str()),
+            Cell::new(status),
+            Cell::new(ranges.as_str()),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// Reads entries from inputs, which are values or files with values one per line, or from stdin if there are no inputs.
+fn read_entries(inputs: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut lines = vec![];
+    if inputs.is_empty() {
+        for line in std::io::stdin().lock().lines() {
+            lines.push(line?);
+        }
+    }
+    for input in inputs {
+        let path = Path::new(input.as_str());
+        if path.exists() && path.is_file() {
+            lines.extend(std::fs::read_to_string(path)?.lines().map(String::from));
+        } else {
+            lines.push(input);
+        }
+    }
+    Ok(lines
+        .into_iter()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect())
+}
+
+fn join_ranges(ranges: &[Cidr]) -> String {
+    ranges
+        .iter()
+        .map(|range| range.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+async fn scan_logs(files: Vec<PathBuf>, top: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let cf_ips = CFIPs::load().await?;
+    let mut stats = LogStats::default();
+    if files.is_empty() {
+        for line in std::io::stdin().lock().lines() {
+            stats.add_line(&line?, &cf_ips);
+        }
+    }
+    for file in files {
+        let reader = std::io::BufReader::new(std::fs::File::open(file)?);
+        for line in reader.lines() {
+            stats.add_line(&line?, &cf_ips);
+        }
+    }
+    let percent = |requests: usize| {
+        if stats.total == 0 {
+            0.0
+        } else {
+            requests as f64 * 100.0 / stats.total as f64
+        }
+    };
+    let mut table = Table::new();
+    table.add_row(row!["Traffic", "Requests", "Share"]);
+    table.add_row(row![
+        "Via Cloudflare",
+        stats.via_cf,
+        format!("{:.2}%", stats.cf_share() * 100.0)
+    ]);
+    table.add_row(row![
+        "Direct",
+        stats.direct_total(),
+        format!("{:.2}%", percent(stats.direct_total()))
+    ]);
+    table.add_row(row!["Unparsed lines", stats.unparsed, ""]);
+    table.printstd();
+    let mut table = Table::new();
+    table.add_row(row!["Direct source", "Requests", "Share"]);
+    for (ip, requests) in stats.top_direct(top) {
+        table.add_row(row![ip, requests, format!("{:.2}%", percent(requests))]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+async fn ranges(command: RangesCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let cf_ips = CFIPs::load().await?;
+    match command {
+        RangesCommand::Export { format } => print!("{}", format.render(&cf_ips)),
+        RangesCommand::Diff { since } => {
+            let snapshot = CFIPs::parse(&std::fs::read_to_string(since)?);
+            let diff = cf_ips.diff(&snapshot);
+            for cidr in diff.added.iter() {
+                println!("+ {}", cidr);
+            }
+            for cidr in diff.removed.iter() {
+                println!("- {}", cidr);
+            }
+            if !diff.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        RangesCommand::Summary => {
+            let mut table = Table::new();
+            table.add_row(row!["Family", "Ranges", "Merged ranges", "Addresses"]);
+            let (ipsv4, ipsv6): (Vec<Cidr>, Vec<Cidr>) =
+                cf_ips.ranges().into_iter().partition(|cidr| cidr.is_ipv4());
+            for (family, ranges) in [("IPv4", ipsv4), ("IPv6", ipsv6)] {
+                table.add_row(row![
+                    family,
+                    ranges.len(),
+                    Cidr::aggregate(&ranges).len(),
+                    Cidr::count_addresses(&ranges)
+                ]);
+            }
+            table.printstd();
+        }
+        RangesCommand::Intersect { inputs } => {
+            let mut cidrs = vec![];
+            for entry in read_entries(inputs)? {
+                cidrs.push(entry.parse::<Cidr>()?);
+            }
+            for cidr in cf_ips.intersection(&cidrs) {
+                println!("{}", cidr);
+            }
+        }
+    }
     Ok(())
 }
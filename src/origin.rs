@@ -0,0 +1,421 @@
+//! Origin IP exposure finder.
+//! It looks for IPs of a CF-proxied apex which bypass CF but answer like the apex does.
+
+use crate::cf_ips::CFIPs;
+use crate::domain::{Domain, Scheme};
+use crate::helpers::{extract_title, sha256_hex};
+use crate::probe::Probe;
+use hickory_resolver::TokioAsyncResolver;
+use std::{collections::BTreeMap, error::Error, net::IpAddr, sync::Arc};
+
+/// Subdomains which commonly point straight to the origin.
+pub const DEFAULT_WORDLIST: &[&str] = &[
+    "www",
+    "direct",
+    "direct-connect",
+    "origin",
+    "origin-www",
+    "server",
+    "host",
+    "mail",
+    "webmail",
+    "smtp",
+    "pop",
+    "imap",
+    "ftp",
+    "cpanel",
+    "whm",
+    "ns1",
+    "ns2",
+    "dev",
+    "staging",
+    "stage",
+    "test",
+    "beta",
+    "api",
+    "admin",
+    "portal",
+    "vpn",
+    "remote",
+    "blog",
+    "shop",
+    "old",
+    "backup",
+];
+
+/// A fingerprint of what a host serves, used to match origins with the proxied host.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// The DER encoded leaf certificate.
+    pub certificate: Option<Vec<u8>>,
+    /// The status code of the page.
+    pub status: Option<u16>,
+    /// The page title.
+    pub title: Option<String>,
+    /// A hex encoded SHA-256 digest of the page body.
+    pub body_hash: Option<String>,
+}
+
+/// A comparison of two fingerprints, field by field.
+/// Fields missing in both fingerprints don't match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Comparison {
+    /// Both serve the same leaf certificate.
+    pub same_certificate: bool,
+    /// Both answer with the same status code.
+    pub same_status: bool,
+    /// Both serve the same page title.
+    pub same_title: bool,
+    /// Both serve the same page body.
+    pub same_body: bool,
+}
+
+impl Fingerprint {
+    /// Fetches the fingerprint of the domain, connecting to `ip` instead of resolving the domain if it's given.
+    /// The page at the domain's URL is fetched, and the certificate on its TLS port, only with the `tls-check` feature.
+    /// Plain HTTP domains on the default port are fetched over HTTPS first, as origins often listen on 443 only.
+    /// #Example:
+    /// ```no_run
+    /// use cfd::{domain::Domain, origin::Fingerprint};
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let domain = Domain::build("cloudflare.com".to_string()).unwrap();
+    ///     let fingerprint = Fingerprint::fetch(&domain, None).await;
+    ///     assert!(fingerprint.certificate.is_some());
+    /// }
+    /// ```
+    pub async fn fetch(domain: &Domain, ip: Option<IpAddr>) -> Self {
+        Fingerprint::fetch_with(&Probe::default(), domain, ip).await
+    }
+
+    /// Fetches the fingerprint like `fetch` does, going through the probe's transport.
+    /// Without an IP the domain is reached as the probe reaches it, resolver overrides included.
+    /// #Example:
+    /// ```
+    /// use cfd::{domain::Domain, origin::Fingerprint, probe::{Probe, ProbePolicy}, transport::{HttpResponse, MockTransport}};
+    /// use std::sync::Arc;
+    /// #[tokio::main]
+    /// async fn main(){
+    ///     let ip = "192.0.2.1".parse().unwrap();
+    ///     let transport = MockTransport::new()
+    ///         .with_direct_response(ip, "https://example.com:8443/app", HttpResponse::new(200).body("<title>Example</title>"));
+    ///     let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+    ///     let domain = Domain::build("example.com:8443/app".to_string()).unwrap();
+    ///     let fingerprint = Fingerprint::fetch_with(&probe, &domain, Some(ip)).await;
+    ///     assert_eq!((fingerprint.status, fingerprint.title.as_deref()), (Some(200), Some("Example")));
+    ///     assert_eq!(fingerprint.certificate, None);
+    /// }
+    /// ```
+    pub async fn fetch_with(probe: &Probe, domain: &Domain, ip: Option<IpAddr>) -> Self {
+        let transport = probe.transport();
+        let (host, port) = (domain.name.as_str(), domain.tls_port());
+        let tls_info = match ip {
+            Some(ip) => transport.tls_handshake_direct(host, port, ip).await,
+            None => transport.tls_handshake(host, port).await,
+        };
+        let mut fingerprint = Self {
+            certificate: tls_info.ok().and_then(|tls_info| tls_info.certificate),
+            ..Default::default()
+        };
+        let mut urls = vec![];
+        if (domain.scheme, domain.port) == (Scheme::Http, None) {
+            let https = Domain {
+                scheme: Scheme::Https,
+                ..domain.clone()
+            };
+            urls.push(https.url());
+        }
+        urls.push(domain.url());
+        for url in urls {
+            let resp = match ip {
+                Some(ip) => transport.get_direct(&url, ip).await,
+                None => transport.get(&url).await,
+            };
+            if let Ok(resp) = resp {
+                fingerprint.status = Some(resp.status);
+                fingerprint.title = extract_title(&resp.body);
+                fingerprint.body_hash = Some(sha256_hex(resp.body.as_bytes()));
+                break;
+            }
+        }
+        fingerprint
+    }
+
+    /// Compares the fingerprint with another one.
+    /// #Example:
+    /// ```
+    /// use cfd::origin::Fingerprint;
+    /// let proxied = Fingerprint { status: Some(200), title: Some("Example".to_string()), ..Default::default() };
+    /// let origin = Fingerprint { status: Some(403), title: Some("Example".to_string()), ..Default::default() };
+    /// let comparison = proxied.compare(&origin);
+    /// assert!(comparison.same_title);
+    /// assert!(!comparison.same_status);
+    /// assert!(!comparison.same_certificate);
+    /// ```
+    pub fn compare(&self, other: &Fingerprint) -> Comparison {
+        Comparison {
+            same_certificate: self.certificate.is_some() && self.certificate == other.certificate,
+            same_status: self.status.is_some() && self.status == other.status,
+            same_title: self.title.is_some() && self.title == other.title,
+            same_body: self.body_hash.is_some() && self.body_hash == other.body_hash,
+        }
+    }
+}
+
+/// An IP of the apex found outside of CF's ranges.
+#[derive(Debug, Clone)]
+pub struct OriginCandidate {
+    /// The candidate IP.
+    pub ip: IpAddr,
+    /// Where the IP was found: a subdomain, an MX host, an SPF or a TXT record.
+    pub source: String,
+    /// If the IP serves the same certificate as the proxied apex, it will be set to true.
+    pub same_certificate: bool,
+    /// If the IP serves the same page title as the proxied apex, it will be set to true.
+    pub same_title: bool,
+}
+
+impl OriginCandidate {
+    /// Checks if the candidate answers like the proxied apex and thus exposes the origin.
+    pub fn is_exposed(&self) -> bool {
+        self.same_certificate || self.same_title
+    }
+}
+
+/// A direct-to-origin probe of an IP compared with the CF-fronted response.
+#[derive(Debug, Clone)]
+pub struct OriginVerification {
+    /// The probed IP.
+    pub ip: IpAddr,
+    /// The fingerprint of the CF-fronted host.
+    pub proxied: Fingerprint,
+    /// The fingerprint of the host served by the IP.
+    pub origin: Fingerprint,
+}
+
+impl OriginVerification {
+    /// Compares the origin's fingerprint with the CF-fronted one.
+    pub fn comparison(&self) -> Comparison {
+        self.proxied.compare(&self.origin)
+    }
+
+    /// Checks if the IP serves the site: the same certificate, the same body,
+    /// or the same status code together with the same title.
+    /// #Example:
+    /// ```
+    /// use cfd::origin::{Fingerprint, OriginVerification};
+    /// let proxied = Fingerprint { status: Some(200), title: Some("Example".to_string()), ..Default::default() };
+    /// let verification = OriginVerification {
+    ///     ip: "192.0.2.1".parse().unwrap(),
+    ///     proxied: proxied.clone(),
+    ///     origin: proxied,
+    /// };
+    /// assert!(verification.is_origin());
+    /// ```
+    pub fn is_origin(&self) -> bool {
+        let comparison = self.comparison();
+        comparison.same_certificate
+            || comparison.same_body
+            || comparison.same_status && comparison.same_title
+    }
+}
+
+/// Looks for origin IPs of a CF-proxied apex.
+/// The apex's subdomains from the wordlist, its MX hosts and IPs from its TXT records are resolved.
+/// Each IP outside of CF's ranges is fingerprinted and compared with the proxied apex.
+/// Fails if the apex doesn't resolve to CF, as there is no proxied response to compare with then.
+/// #Example:
+/// ```no_run
+/// use cfd::{cf_ips::CFIPs, origin::{hunt, DEFAULT_WORDLIST}};
+/// use std::sync::Arc;
+/// #[tokio::main]
+/// async fn main(){
+///     let cf_ips = Arc::new(CFIPs::load().await.unwrap());
+///     let wordlist = DEFAULT_WORDLIST.iter().map(|word| word.to_string()).collect::<Vec<_>>();
+///     let candidates = hunt("cloudflare.com", &wordlist, cf_ips).await.unwrap();
+///     assert!(candidates.iter().all(|candidate| !candidate.is_exposed()));
+/// }
+/// ```
+pub async fn hunt(
+    apex: &str,
+    wordlist: &[String],
+    cf_ips: Arc<CFIPs>,
+) -> Result<Vec<OriginCandidate>, Box<dyn Error>> {
+    hunt_with(apex, wordlist, cf_ips, &Probe::default()).await
+}
+
+/// Looks for origin IPs like `hunt` does, fingerprinting them through the probe's transport.
+/// DNS lookups go to the system resolver.
+pub async fn hunt_with(
+    apex: &str,
+    wordlist: &[String],
+    cf_ips: Arc<CFIPs>,
+    probe: &Probe,
+) -> Result<Vec<OriginCandidate>, Box<dyn Error>> {
+    let domain = Domain::build(apex.to_string())?;
+    let apex = domain.name.as_str();
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+    let apex_ips = resolver
+        .lookup_ip(apex)
+        .await
+        .map(|lookup| lookup.iter().collect::<Vec<IpAddr>>())
+        .unwrap_or_default();
+    let ips = collect_ips(&resolver, apex, wordlist).await;
+    let ips = candidate_ips(apex, &apex_ips, ips, &cf_ips)?;
+    compare_candidates(&domain, ips, probe).await
+}
+
+/// Fingerprints each candidate IP and compares it with the proxied apex.
+/// #Example:
+/// ```
+/// use cfd::{domain::Domain, origin::compare_candidates, probe::{Probe, ProbePolicy}, transport::{HttpResponse, MockTransport}};
+/// use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
+/// #[tokio::main]
+/// async fn main(){
+///     let (origin, other): (IpAddr, IpAddr) = ("192.0.2.10".parse().unwrap(), "192.0.2.20".parse().unwrap());
+///     let page = || HttpResponse::new(200).body("<title>Example</title>");
+///     let transport = MockTransport::new()
+///         .with_response("https://example.com", page())
+///         .with_direct_response(origin, "https://example.com", page())
+///         .with_direct_response(other, "https://example.com", HttpResponse::new(404));
+///     let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+///     let ips = BTreeMap::from([(origin, "direct.example.com".to_string()), (other, "SPF".to_string())]);
+///     let apex = Domain::build("example.com".to_string()).unwrap();
+///     let candidates = compare_candidates(&apex, ips, &probe).await.unwrap();
+///     let exposed: Vec<IpAddr> = candidates.iter().filter(|candidate| candidate.is_exposed()).map(|candidate| candidate.ip).collect();
+///     assert_eq!(exposed, vec![origin]);
+/// }
+/// ```
+pub async fn compare_candidates(
+    apex: &Domain,
+    ips: BTreeMap<IpAddr, String>,
+    probe: &Probe,
+) -> Result<Vec<OriginCandidate>, Box<dyn Error>> {
+    let proxied = Fingerprint::fetch_with(probe, apex, None).await;
+    let mut handles = vec![];
+    for (ip, source) in ips {
+        let apex = apex.clone();
+        let proxied = proxied.clone();
+        let probe = probe.clone();
+        let handle = tokio::spawn(async move {
+            let origin = Fingerprint::fetch_with(&probe, &apex, Some(ip)).await;
+            let comparison = proxied.compare(&origin);
+            OriginCandidate {
+                ip,
+                source,
+                same_certificate: comparison.same_certificate,
+                same_title: comparison.same_title,
+            }
+        });
+        handles.push(handle);
+    }
+    let mut candidates = vec![];
+    for handle in handles {
+        candidates.push(handle.await?);
+    }
+    Ok(candidates)
+}
+
+/// Picks origin candidates out of IPs found for the apex: ones outside of CF's ranges which the apex itself doesn't resolve to.
+/// Fails if none of the apex's own IPs is in CF's ranges, i.e. the apex isn't proxied by CF.
+/// #Example:
+/// ```
+/// use cfd::{cf_ips::CFIPs, origin::candidate_ips};
+/// use std::{collections::BTreeMap, net::IpAddr};
+/// let cf_ips = CFIPs::parse("104.16.0.0/13");
+/// let ips: BTreeMap<IpAddr, String> = [
+///     ("104.16.1.1", "www.example.com"),
+///     ("192.0.2.10", "direct.example.com"),
+///     ("198.51.100.7", "SPF"),
+/// ]
+/// .into_iter()
+/// .map(|(ip, source)| (ip.parse().unwrap(), source.to_string()))
+/// .collect();
+/// let apex_ips: Vec<IpAddr> = vec!["104.16.1.1".parse().unwrap(), "198.51.100.7".parse().unwrap()];
+/// let candidates = candidate_ips("example.com", &apex_ips, ips.clone(), &cf_ips).unwrap();
+/// assert_eq!(candidates.into_keys().collect::<Vec<_>>(), vec!["192.0.2.10".parse::<IpAddr>().unwrap()]);
+/// let unproxied: Vec<IpAddr> = vec!["198.51.100.7".parse().unwrap()];
+/// assert!(candidate_ips("example.com", &unproxied, ips, &cf_ips).is_err());
+/// ```
+pub fn candidate_ips(
+    apex: &str,
+    apex_ips: &[IpAddr],
+    ips: BTreeMap<IpAddr, String>,
+    cf_ips: &CFIPs,
+) -> Result<BTreeMap<IpAddr, String>, String> {
+    if !apex_ips.iter().any(|ip| cf_ips.check_ip(ip)) {
+        return Err(format!("{} doesn't resolve to CF's ranges", apex));
+    }
+    Ok(ips
+        .into_iter()
+        .filter(|(ip, _)| !apex_ips.contains(ip) && !cf_ips.check_ip(ip))
+        .collect())
+}
+
+/// Resolves the apex's subdomains, MX hosts and TXT records to IPs, mapped to where they were found.
+async fn collect_ips(
+    resolver: &TokioAsyncResolver,
+    apex: &str,
+    wordlist: &[String],
+) -> BTreeMap<IpAddr, String> {
+    let mut ips = BTreeMap::new();
+    for host in wordlist.iter().map(|word| format!("{}.{}", word, apex)) {
+        if let Ok(lookup) = resolver.lookup_ip(host.as_str()).await {
+            for ip in lookup.iter() {
+                ips.entry(ip).or_insert_with(|| host.clone());
+            }
+        }
+    }
+    if let Ok(lookup) = resolver.mx_lookup(apex).await {
+        for mx in lookup.iter() {
+            let exchange = mx.exchange().to_utf8();
+            if let Ok(lookup) = resolver.lookup_ip(exchange.as_str()).await {
+                for ip in lookup.iter() {
+                    ips.entry(ip).or_insert_with(|| format!("MX {}", exchange));
+                }
+            }
+        }
+    }
+    if let Ok(lookup) = resolver.txt_lookup(apex).await {
+        for txt in lookup.iter() {
+            let txt = txt.to_string();
+            let source = if txt.starts_with("v=spf1") {
+                "SPF"
+            } else {
+                "TXT"
+            };
+            for ip in parse_txt_ips(&txt) {
+                ips.entry(ip).or_insert_with(|| source.to_string());
+            }
+        }
+    }
+    ips
+}
+
+/// Extracts IPs from a TXT record, including SPF's `ip4:` and `ip6:` mechanisms.
+/// SPF networks wider than a single host are skipped.
+/// #Example:
+/// ```
+/// use cfd::origin::parse_txt_ips;
+/// use std::net::IpAddr;
+/// let ips = parse_txt_ips("v=spf1 ip4:192.0.2.10 ip4:198.51.100.0/24 ip6:2001:db8::1/128 -all");
+/// let expected: Vec<IpAddr> = vec!["192.0.2.10".parse().unwrap(), "2001:db8::1".parse().unwrap()];
+/// assert_eq!(ips, expected);
+/// let ips = parse_txt_ips("server=203.0.113.7");
+/// assert_eq!(ips, vec!["203.0.113.7".parse::<IpAddr>().unwrap()]);
+/// ```
+pub fn parse_txt_ips(txt: &str) -> Vec<IpAddr> {
+    txt.split(|c: char| c.is_whitespace() || c == ';' || c == ',' || c == '=')
+        .map(|token| {
+            token
+                .trim_start_matches('+')
+                .trim_start_matches("ip4:")
+                .trim_start_matches("ip6:")
+        })
+        .filter_map(|token| match token.split_once('/') {
+            Some((ip, "32" | "128")) => ip.parse().ok(),
+            Some(_) => None,
+            None => token.parse().ok(),
+        })
+        .collect()
+}
@@ -0,0 +1,120 @@
+//! Probe settings shared by domain checks.
+//! It holds the probe policy, client options and the transport built from them.
+
+use crate::transport::{HttpTransport, Transport};
+use std::{collections::HashMap, error::Error, net::SocketAddr, sync::Arc, time::Duration};
+
+/// How domains are probed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbePolicy {
+    /// A timeout for each HTTP request and TLS handshake.
+    pub timeout: Duration,
+    /// How many times a failed HTTP probe is retried before the domain is marked unreachable.
+    pub retries: u32,
+    /// How many redirects the HTTP probe follows.
+    pub max_redirects: usize,
+}
+
+impl Default for ProbePolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            retries: 0,
+            max_redirects: 10,
+        }
+    }
+}
+
+/// How the HTTP transport reaches hosts.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    /// The user agent of HTTP probes.
+    pub user_agent: Option<String>,
+    /// A proxy HTTP probes go through, e.g. `http://127.0.0.1:8080` or `socks5://127.0.0.1:1080`.
+    pub proxy: Option<String>,
+    /// Hosts connected to at fixed addresses instead of querying DNS.
+    /// A port of 0 keeps the scheme's default one, other ports apply to plain HTTP and TLS probes.
+    pub resolve: HashMap<String, SocketAddr>,
+    /// DER encoded certificates trusted in addition to the webpki roots.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// If it's set, the TLS probe doesn't fail on invalid certificates but records why they are invalid,
+    /// and HTTPS pages are fetched whatever certificates they are served with.
+    pub inspect_certificates: bool,
+}
+
+impl ClientOptions {
+    /// Reads certificates from a CA bundle: PEM encoded ones or a single DER encoded one.
+    /// #Example:
+    /// ```
+    /// use cfd::probe::ClientOptions;
+    /// let path = std::env::temp_dir().join("cfd_empty_bundle.pem");
+    /// std::fs::write(&path, "not a certificate").unwrap();
+    /// assert!(ClientOptions::read_ca_file(&path).is_err());
+    /// assert!(ClientOptions::read_ca_file("missing.pem").is_err());
+    /// ```
+    #[cfg(any(feature = "cli", feature = "tls-check"))]
+    pub fn read_ca_file(path: impl AsRef<std::path::Path>) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        if !bytes.windows(10).any(|window| window == b"-----BEGIN") {
+            // A DER encoded certificate is an ASN.1 sequence.
+            return match bytes.first() {
+                Some(0x30) => Ok(vec![bytes]),
+                _ => Err(format!("No certificates in {}", path.display()).into()),
+            };
+        }
+        let certificates = rustls_pemfile::certs(&mut bytes.as_slice())?;
+        if certificates.is_empty() {
+            return Err(format!("No certificates in {}", path.display()).into());
+        }
+        Ok(certificates)
+    }
+}
+
+/// Settings and a transport shared by domain checks.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    /// How domains are probed.
+    pub policy: ProbePolicy,
+    transport: Arc<dyn Transport>,
+}
+
+impl Probe {
+    /// Builds a new probe which talks to the network.
+    /// #Example:
+    /// ```
+    /// use cfd::probe::{ClientOptions, Probe, ProbePolicy};
+    /// let mut options = ClientOptions { user_agent: Some("cfd".to_string()), ..Default::default() };
+    /// options.resolve.insert("example.com".to_string(), "192.0.2.1:0".parse().unwrap());
+    /// assert!(Probe::new(ProbePolicy::default(), &options).is_ok());
+    /// options.proxy = Some("not a proxy".to_string());
+    /// assert!(Probe::new(ProbePolicy::default(), &options).is_err());
+    /// ```
+    pub fn new(policy: ProbePolicy, options: &ClientOptions) -> Result<Self, Box<dyn Error>> {
+        let transport = HttpTransport::new(&policy, options)?;
+        Ok(Self::with_transport(policy, Arc::new(transport)))
+    }
+
+    /// Builds a new probe which goes through the given transport.
+    /// #Example:
+    /// ```
+    /// use cfd::{probe::{Probe, ProbePolicy}, transport::MockTransport};
+    /// use std::sync::Arc;
+    /// let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(MockTransport::new()));
+    /// assert_eq!(probe.policy, ProbePolicy::default());
+    /// ```
+    pub fn with_transport(policy: ProbePolicy, transport: Arc<dyn Transport>) -> Self {
+        Self { policy, transport }
+    }
+
+    /// Returns the transport.
+    pub fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+}
+
+impl Default for Probe {
+    fn default() -> Self {
+        Self::with_transport(ProbePolicy::default(), Arc::new(HttpTransport::default()))
+    }
+}
@@ -0,0 +1,47 @@
+//! Public suffix lookups against an embedded snapshot of the Public Suffix List.
+//! The snapshot, from 2023-02-09, is `resources/public_suffix_list.dat`, covered by the MPL 2.0.
+
+use publicsuffix::{List, Psl};
+use std::sync::OnceLock;
+
+/// The embedded snapshot of the list.
+const SNAPSHOT: &str = include_str!("../resources/public_suffix_list.dat");
+
+/// Returns the list, parsing the snapshot on first use.
+fn list() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        SNAPSHOT
+            .parse()
+            .expect("The embedded public suffix list is invalid")
+    })
+}
+
+/// Returns the public suffix (eTLD) of a lowercase punycode name.
+/// Names under TLDs missing from the list get the last label as their suffix.
+/// #Example:
+/// ```
+/// use cfd::public_suffix;
+/// assert_eq!(public_suffix::suffix("www.example.co.uk").as_deref(), Some("co.uk"));
+/// assert_eq!(public_suffix::suffix("foo.example.invalidtld").as_deref(), Some("invalidtld"));
+/// ```
+pub fn suffix(name: &str) -> Option<String> {
+    list()
+        .suffix(name.as_bytes())
+        .map(|suffix| String::from_utf8_lossy(suffix.as_bytes()).to_string())
+}
+
+/// Returns the registrable domain (eTLD+1) of a lowercase punycode name.
+/// Public suffixes themselves have none.
+/// #Example:
+/// ```
+/// use cfd::public_suffix;
+/// assert_eq!(public_suffix::registrable_domain("a.b.example.co.uk").as_deref(), Some("example.co.uk"));
+/// assert_eq!(public_suffix::registrable_domain("foo.github.io").as_deref(), Some("foo.github.io"));
+/// assert_eq!(public_suffix::registrable_domain("co.uk"), None);
+/// ```
+pub fn registrable_domain(name: &str) -> Option<String> {
+    list()
+        .domain(name.as_bytes())
+        .map(|domain| String::from_utf8_lossy(domain.as_bytes()).to_string())
+}
@@ -0,0 +1,105 @@
+//! Target list normalisation.
+//! It turns raw input lines into unique domains and tells which lines were rejected and why.
+
+use crate::domain::Domain;
+use std::{collections::HashMap, fmt};
+
+/// An input line which didn't make it into the target list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejected {
+    /// The 1-based number of the line.
+    pub line: usize,
+    /// The line as it was given, trimmed.
+    pub input: String,
+    /// Why the line was rejected.
+    pub reason: String,
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}: {}", self.line, self.input, self.reason)
+    }
+}
+
+/// Normalised targets together with rejected lines.
+#[derive(Debug, Clone, Default)]
+pub struct Targets {
+    /// Unique domains in the order they were given.
+    pub domains: Vec<Domain>,
+    /// Lines which are invalid or duplicate earlier ones.
+    pub rejected: Vec<Rejected>,
+}
+
+impl Targets {
+    /// Normalises input lines into unique domains. Blank lines are skipped.
+    /// Names are lowercased and lose the trailing dot, the scheme's default port and a bare `/` path.
+    /// A leading `*.` label is expanded to the parent domain, other wildcards are rejected.
+    /// Lines which end up as an earlier target are rejected as duplicates.
+    /// #Example:
+    /// ```
+    /// use cfd::targets::Targets;
+    /// let input = "WWW.Example.com.\nexample.com:443\n*.example.com\nhttps://example.com/\nwww.example.com\nexample.*\n\n-example.com";
+    /// let targets = Targets::parse(input.lines());
+    /// let domains: Vec<String> = targets.domains.iter().map(|domain| domain.url()).collect();
+    /// assert_eq!(domains, vec!["http://www.example.com", "https://example.com", "http://example.com"]);
+    /// // Targets which differ in the scheme only are told apart by it.
+    /// let shown: Vec<String> = targets.domains.iter().map(|domain| domain.target()).collect();
+    /// assert_eq!(shown, vec!["www.example.com", "https://example.com", "example.com"]);
+    /// let rejected: Vec<usize> = targets.rejected.iter().map(|rejected| rejected.line).collect();
+    /// assert_eq!(rejected, vec![4, 5, 6, 8]);
+    /// assert_eq!(targets.rejected[0].reason, "Duplicate of line 2");
+    /// // A URL in the query isn't taken for the target's scheme.
+    /// let targets = Targets::parse(["*.example.com/login?next=https://x"]);
+    /// assert_eq!(targets.domains[0].url(), "http://example.com/login?next=https://x");
+    /// ```
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut targets = Targets::default();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for (index, input) in lines.into_iter().enumerate() {
+            let line = index + 1;
+            let input = input.trim();
+            if input.is_empty() {
+                continue;
+            }
+            let mut reject = |reason: String| {
+                targets.rejected.push(Rejected {
+                    line,
+                    input: input.to_string(),
+                    reason,
+                })
+            };
+            let domain = match Targets::expand_wildcard(input).and_then(Domain::build) {
+                Ok(domain) => domain,
+                Err(reason) => {
+                    reject(reason);
+                    continue;
+                }
+            };
+            match seen.get(&domain.url()) {
+                Some(first) => reject(format!("Duplicate of line {}", first)),
+                None => {
+                    seen.insert(domain.url(), line);
+                    targets.domains.push(domain);
+                }
+            }
+        }
+        targets
+    }
+
+    /// Replaces a leading `*.` label of the host with nothing, so the parent domain is checked.
+    /// Wildcards anywhere else in the host can't be expanded, ones in the path are left as they are.
+    fn expand_wildcard(input: &str) -> Result<String, String> {
+        let (scheme, rest) = match Domain::split_scheme(input) {
+            (Some(scheme), rest) => (format!("{}://", scheme), rest),
+            (None, rest) => (String::new(), rest),
+        };
+        let host_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        if !rest[..host_end].contains('*') {
+            return Ok(input.to_string());
+        }
+        match rest.strip_prefix("*.") {
+            Some(parent) if !parent[..host_end - 2].contains('*') => Ok(scheme + parent),
+            _ => Err("Wildcards are only supported as a leading *. label".to_string()),
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Transports which domain checks, origin probes and range downloads go through.
+//! The HTTP one talks to the network, the mock one answers from recorded responses so checks can run offline.
+
+use crate::domain::TlsInfo;
+#[cfg(feature = "tls-check")]
+use crate::domain::{CertValidation, Domain};
+use crate::probe::{ClientOptions, ProbePolicy};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, HOST};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+/// An error a transport fails with. It can be sent between tasks.
+pub type TransportError = Box<dyn Error + Send + Sync>;
+
+/// An HTTP response as domain checks see it.
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponse {
+    /// The status code.
+    pub status: u16,
+    /// The response headers.
+    pub headers: HeaderMap,
+    /// The response body.
+    pub body: String,
+    /// The address the response came from.
+    pub remote_addr: Option<SocketAddr>,
+}
+
+impl HttpResponse {
+    /// Builds a new response with a status code and without headers and body.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a header. Invalid names and values are skipped.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            self.headers.append(name, value);
+        }
+        self
+    }
+
+    /// Sets the body.
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets the IP the response came from. The port is always 80, checks only look at the IP.
+    pub fn remote_ip(mut self, ip: IpAddr) -> Self {
+        self.remote_addr = Some(SocketAddr::new(ip, 80));
+        self
+    }
+
+    /// Checks if the status code is a successful one.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// A way to reach hosts for HTTP requests and TLS handshakes.
+#[async_trait]
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Sends a GET request to the URL.
+    async fn get(&self, url: &str) -> Result<HttpResponse, TransportError>;
+
+    /// Performs a TLS handshake with the host on the port, verifying its certificate.
+    /// In inspection mode invalid certificates don't fail it, the validation error is recorded instead.
+    async fn tls_handshake(&self, host: &str, port: u16) -> Result<TlsInfo, TransportError>;
+
+    /// Sends a GET request to the URL, connecting to `ip` instead of resolving its host.
+    /// Any certificate is accepted, as origins often serve ones which aren't publicly trusted.
+    async fn get_direct(&self, url: &str, ip: IpAddr) -> Result<HttpResponse, TransportError>;
+
+    /// Performs a TLS handshake with the host on the port, connecting to `ip` instead of resolving the host.
+    /// Any certificate is accepted, so self-signed origins can be fingerprinted too.
+    async fn tls_handshake_direct(
+        &self,
+        host: &str,
+        port: u16,
+        ip: IpAddr,
+    ) -> Result<TlsInfo, TransportError>;
+}
+
+/// A transport which talks to the network.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    options: ClientOptions,
+    policy: ProbePolicy,
+}
+
+impl HttpTransport {
+    /// Builds a new HTTP transport.
+    /// #Example:
+    /// ```
+    /// use cfd::{probe::{ClientOptions, ProbePolicy}, transport::HttpTransport};
+    /// let options = ClientOptions { user_agent: Some("cfd".to_string()), ..Default::default() };
+    /// assert!(HttpTransport::new(&ProbePolicy::default(), &options).is_ok());
+    /// let options = ClientOptions { root_certificates: vec![b"not a certificate".to_vec()], ..Default::default() };
+    /// assert!(HttpTransport::new(&ProbePolicy::default(), &options).is_err());
+    /// ```
+    pub fn new(policy: &ProbePolicy, options: &ClientOptions) -> Result<Self, Box<dyn Error>> {
+        // Hosts are probed in punycode, so overrides for internationalized names are keyed by it too.
+        let mut options = options.clone();
+        options.resolve = options
+            .resolve
+            .into_iter()
+            .map(|(host, addr)| (idna::domain_to_ascii(&host).unwrap_or(host), addr))
+            .collect();
+        Ok(Self {
+            client: HttpTransport::client_builder(policy, &options)?.build()?,
+            options,
+            policy: *policy,
+        })
+    }
+
+    /// Returns a client builder with the policy's timeout and redirects, and the user agent, proxy,
+    /// resolver overrides and trusted certificates of the options.
+    fn client_builder(
+        policy: &ProbePolicy,
+        options: &ClientOptions,
+    ) -> Result<reqwest::ClientBuilder, Box<dyn Error>> {
+        let redirect = match policy.max_redirects {
+            0 => reqwest::redirect::Policy::none(),
+            max => reqwest::redirect::Policy::limited(max),
+        };
+        let mut builder = reqwest::Client::builder()
+            .timeout(policy.timeout)
+            .redirect(redirect);
+        if let Some(user_agent) = &options.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(proxy) = &options.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        for (host, addr) in options.resolve.iter() {
+            // The port is ignored by reqwest, other ports are handled in `get`.
+            builder = builder.resolve(host, *addr);
+        }
+        for certificate in options.root_certificates.iter() {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_der(certificate)?);
+        }
+        if options.inspect_certificates {
+            // The TLS probe records why a certificate is invalid, so pages behind it are still fetched.
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+
+    /// Reads a response as domain checks see it.
+    async fn read_response(resp: reqwest::Response) -> HttpResponse {
+        let status = resp.status().as_u16();
+        let headers = resp.headers().clone();
+        let remote_addr = resp.remote_addr();
+        let body = resp.text().await.unwrap_or_default();
+        HttpResponse {
+            status,
+            headers,
+            body,
+            remote_addr,
+        }
+    }
+
+    /// Returns the address a host is connected to by a resolver override.
+    pub fn resolved_addr(&self, host: &str) -> Option<SocketAddr> {
+        self.options.resolve.get(host).copied()
+    }
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new(&ProbePolicy::default(), &ClientOptions::default())
+            .expect("Failed to build the default HTTP client")
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn get(&self, url: &str) -> Result<HttpResponse, TransportError> {
+        let mut url = reqwest::Url::parse(url)?;
+        let mut host_header = None;
+        // reqwest ignores ports of resolver overrides, so plain HTTP requests are sent to them directly.
+        if let Some(host) = url.host_str().map(|host| host.to_string()) {
+            let addr = self.resolved_addr(&host).filter(|addr| addr.port() != 0);
+            if let (Some(addr), "http") = (addr, url.scheme()) {
+                url.set_ip_host(addr.ip())
+                    .map_err(|_| format!("Can't connect {} to {}", host, addr))?;
+                url.set_port(Some(addr.port()))
+                    .map_err(|_| format!("Can't connect {} to {}", host, addr))?;
+                host_header = Some(host);
+            }
+        }
+        let mut request = self.client.get(url);
+        if let Some(host) = host_header {
+            request = request.header(HOST, host);
+        }
+        Ok(HttpTransport::read_response(request.send().await?).await)
+    }
+
+    #[cfg(feature = "tls-check")]
+    async fn tls_handshake(&self, host: &str, port: u16) -> Result<TlsInfo, TransportError> {
+        let addr = self.resolved_addr(host);
+        let host = host.to_string();
+        let roots = self.options.root_certificates.clone();
+        let inspect = self.options.inspect_certificates;
+        let timeout = self.policy.timeout;
+        tokio::task::spawn_blocking(move || {
+            let validation = if inspect {
+                CertValidation::Inspect(&roots)
+            } else {
+                CertValidation::Strict(&roots)
+            };
+            Domain::tls_handshake(&host, port, addr, validation, timeout).map_err(|e| e.to_string())
+        })
+        .await?
+        .map_err(|e| e.into())
+    }
+
+    #[cfg(not(feature = "tls-check"))]
+    async fn tls_handshake(&self, _host: &str, _port: u16) -> Result<TlsInfo, TransportError> {
+        Err("The TLS probe requires the tls-check feature".into())
+    }
+
+    async fn get_direct(&self, url: &str, ip: IpAddr) -> Result<HttpResponse, TransportError> {
+        let host = reqwest::Url::parse(url)?
+            .host_str()
+            .ok_or_else(|| format!("No host in {}", url))?
+            .to_string();
+        // Redirects within the host go to the IP too. The port is taken from the URL.
+        let client = HttpTransport::client_builder(&self.policy, &self.options)
+            .map_err(|e| e.to_string())?
+            .resolve(&host, SocketAddr::new(ip, 0))
+            .danger_accept_invalid_certs(true)
+            .build()?;
+        Ok(HttpTransport::read_response(client.get(url).send().await?).await)
+    }
+
+    #[cfg(feature = "tls-check")]
+    async fn tls_handshake_direct(
+        &self,
+        host: &str,
+        port: u16,
+        ip: IpAddr,
+    ) -> Result<TlsInfo, TransportError> {
+        let host = host.to_string();
+        let timeout = self.policy.timeout;
+        tokio::task::spawn_blocking(move || {
+            Domain::tls_handshake(
+                &host,
+                port,
+                Some(SocketAddr::new(ip, port)),
+                CertValidation::Skip,
+                timeout,
+            )
+            .map_err(|e| e.to_string())
+        })
+        .await?
+        .map_err(|e| e.into())
+    }
+
+    #[cfg(not(feature = "tls-check"))]
+    async fn tls_handshake_direct(
+        &self,
+        _host: &str,
+        _port: u16,
+        _ip: IpAddr,
+    ) -> Result<TlsInfo, TransportError> {
+        Err("The TLS probe requires the tls-check feature".into())
+    }
+}
+
+/// A transport which answers from recorded responses and never touches the network.
+/// Requests without a recorded response fail as unreachable hosts do.
+/// #Example:
+/// ```
+/// use cfd::transport::{HttpResponse, MockTransport, Transport};
+/// #[tokio::main]
+/// async fn main(){
+///     let transport = MockTransport::new()
+///         .with_response("http://example.com", HttpResponse::new(200).header("server", "cloudflare"));
+///     let resp = transport.get("http://example.com").await.unwrap();
+///     assert_eq!(resp.headers["server"], "cloudflare");
+///     assert!(transport.get("http://example.org").await.is_err());
+///     assert!(transport.tls_handshake("example.com", 443).await.is_err());
+///     let ip = "192.0.2.1".parse().unwrap();
+///     let transport = transport.with_direct_response(ip, "https://example.com/", HttpResponse::new(403));
+///     assert_eq!(transport.get_direct("https://example.com/", ip).await.unwrap().status, 403);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    responses: HashMap<String, HttpResponse>,
+    handshakes: HashMap<String, TlsInfo>,
+    direct_responses: HashMap<(IpAddr, String), HttpResponse>,
+    direct_handshakes: HashMap<(IpAddr, String), TlsInfo>,
+}
+
+impl MockTransport {
+    /// Builds a new transport without recorded responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a response to a GET request to the URL.
+    pub fn with_response(mut self, url: impl Into<String>, resp: HttpResponse) -> Self {
+        self.responses.insert(url.into(), resp);
+        self
+    }
+
+    /// Records what a TLS handshake with the host sees, on any port or, for `host:port`, on that one.
+    pub fn with_tls(mut self, host: impl Into<String>, tls_info: TlsInfo) -> Self {
+        self.handshakes.insert(host.into(), tls_info);
+        self
+    }
+
+    /// Records a response to a GET request to the URL sent directly to the IP.
+    pub fn with_direct_response(
+        mut self,
+        ip: IpAddr,
+        url: impl Into<String>,
+        resp: HttpResponse,
+    ) -> Self {
+        self.direct_responses.insert((ip, url.into()), resp);
+        self
+    }
+
+    /// Records what a TLS handshake with the host at the IP sees, on any port or, for `host:port`, on that one.
+    pub fn with_direct_tls(
+        mut self,
+        ip: IpAddr,
+        host: impl Into<String>,
+        tls_info: TlsInfo,
+    ) -> Self {
+        self.direct_handshakes.insert((ip, host.into()), tls_info);
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for MockTransport {
+    async fn get(&self, url: &str) -> Result<HttpResponse, TransportError> {
+        self.responses
+            .get(url)
+            .cloned()
+            .ok_or_else(|| format!("No response recorded for {}", url).into())
+    }
+
+    async fn tls_handshake(&self, host: &str, port: u16) -> Result<TlsInfo, TransportError> {
+        self.handshakes
+            .get(&format!("{}:{}", host, port))
+            .or_else(|| self.handshakes.get(host))
+            .cloned()
+            .ok_or_else(|| format!("No handshake recorded for {}", host).into())
+    }
+
+    async fn get_direct(&self, url: &str, ip: IpAddr) -> Result<HttpResponse, TransportError> {
+        self.direct_responses
+            .get(&(ip, url.to_string()))
+            .cloned()
+            .ok_or_else(|| format!("No response recorded for {} at {}", url, ip).into())
+    }
+
+    async fn tls_handshake_direct(
+        &self,
+        host: &str,
+        port: u16,
+        ip: IpAddr,
+    ) -> Result<TlsInfo, TransportError> {
+        self.direct_handshakes
+            .get(&(ip, format!("{}:{}", host, port)))
+            .or_else(|| self.direct_handshakes.get(&(ip, host.to_string())))
+            .cloned()
+            .ok_or_else(|| format!("No handshake recorded for {} at {}", host, ip).into())
+    }
+}
@@ -0,0 +1,429 @@
+//! Offline checks of every `check_result` bit through a mock transport.
+
+use cfd::{
+    cf_ips::{CFIPs, IPS_V4_URL, IPS_V6_URL},
+    checker::Checker,
+    domain::{check_result, Domain, TlsInfo},
+    probe::{Probe, ProbePolicy},
+    transport::{HttpResponse, MockTransport},
+};
+use std::sync::Arc;
+
+const HOST: &str = "example.com";
+const URL: &str = "http://example.com";
+const TRACE_URL: &str = "http://example.com/cdn-cgi/trace";
+const TRACE_BODY: &str = "fl=123f45\nh=example.com\nip=192.0.2.1\nts=1700000000.123\nvisit_scheme=http\nuag=curl/8.0\ncolo=AMS\nhttp=http/1.1\nloc=NL\ntls=off\nsni=off\nwarp=off\ngateway=off\n";
+
+fn cf_ips() -> Arc<CFIPs> {
+    Arc::new(CFIPs::parse("104.16.0.0/13\n2606:4700::/32"))
+}
+
+fn cf_tls() -> TlsInfo {
+    TlsInfo {
+        issuers: vec!["C=US, O=Cloudflare, Inc., CN=Cloudflare Inc ECC CA-3".to_string()],
+        alpn: Some("h2".to_string()),
+        certificate: None,
+        names: vec![],
+        verification_error: None,
+    }
+}
+
+/// What an origin with a CF Origin CA certificate serves, seen in inspection mode.
+fn origin_ca_tls() -> TlsInfo {
+    TlsInfo {
+        issuers: vec!["O=CloudFlare, Inc., OU=CloudFlare Origin SSL Certificate Authority, CN=CloudFlare Origin SSL Certificate Authority".to_string()],
+        verification_error: Some("invalid peer certificate: UnknownIssuer".to_string()),
+        ..Default::default()
+    }
+}
+
+/// Checks the domain through the transport with every detector enabled.
+async fn check(transport: MockTransport) -> Domain {
+    let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+    let mut domain = Domain::build(HOST.to_string())
+        .unwrap()
+        .with_detectors(check_result::DEFAULT | check_result::CF_TRACE);
+    domain.verify_domain_with(cf_ips(), &probe).await.unwrap();
+    domain
+}
+
+#[tokio::test]
+async fn plain_origin_sets_no_bits() {
+    let transport = MockTransport::new().with_response(
+        URL,
+        HttpResponse::new(200)
+            .header("server", "nginx")
+            .remote_ip("192.0.2.1".parse().unwrap()),
+    );
+    let domain = check(transport).await;
+    assert_eq!(domain.check_result, check_result::EMPTY);
+    assert!(!domain.is_unreachable);
+    assert_eq!(domain.get_status(), "CF not detected");
+}
+
+#[tokio::test]
+async fn cf_ip_bit() {
+    for ip in ["104.16.1.1", "2606:4700::6810:84e5"] {
+        let transport = MockTransport::new()
+            .with_response(URL, HttpResponse::new(200).remote_ip(ip.parse().unwrap()));
+        assert_eq!(check(transport).await.check_result, check_result::CF_IP);
+    }
+}
+
+#[tokio::test]
+async fn cf_ray_header_bit() {
+    let transport = MockTransport::new().with_response(
+        URL,
+        HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"),
+    );
+    assert_eq!(
+        check(transport).await.check_result,
+        check_result::CF_RAY_HEADER
+    );
+}
+
+#[tokio::test]
+async fn cf_cache_status_header_bit() {
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200).header("cf-cache-status", "HIT"));
+    assert_eq!(
+        check(transport).await.check_result,
+        check_result::CF_CACHE_STATUS_HEADER
+    );
+}
+
+#[tokio::test]
+async fn cf_server_header_bit() {
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200).header("server", "cloudflare"));
+    assert_eq!(check(transport).await.check_result, check_result::CF_SERVER);
+}
+
+#[tokio::test]
+async fn cf_ssl_bit() {
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_tls(HOST, cf_tls());
+    let domain = check(transport).await;
+    assert_eq!(domain.check_result, check_result::CF_SSL);
+    assert!(domain.has_http2());
+}
+
+#[tokio::test]
+async fn foreign_issuer_doesnt_set_cf_ssl_bit() {
+    let tls_info = TlsInfo {
+        issuers: vec!["C=US, O=Let's Encrypt, CN=R3".to_string()],
+        ..Default::default()
+    };
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_tls(HOST, tls_info);
+    assert_eq!(check(transport).await.check_result, check_result::EMPTY);
+}
+
+#[tokio::test]
+async fn invalid_cf_certificate_doesnt_set_cf_ssl_bit() {
+    let tls_info = TlsInfo {
+        verification_error: Some("invalid peer certificate: UnknownIssuer".to_string()),
+        ..cf_tls()
+    };
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_tls(HOST, tls_info);
+    let domain = check(transport).await;
+    assert_eq!(domain.check_result, check_result::EMPTY);
+    assert_eq!(
+        domain.tls_error.as_deref(),
+        Some("invalid peer certificate: UnknownIssuer")
+    );
+    assert!(domain.has_http2());
+}
+
+#[tokio::test]
+async fn universal_ssl_certificate_sets_cf_ssl_bit() {
+    let tls_info = TlsInfo {
+        issuers: vec!["C=US, O=Google Trust Services, CN=WE1".to_string()],
+        names: vec!["sni.cloudflaressl.com".to_string(), HOST.to_string()],
+        ..Default::default()
+    };
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_tls(HOST, tls_info);
+    assert_eq!(check(transport).await.check_result, check_result::CF_SSL);
+}
+
+#[tokio::test]
+async fn cf_origin_ca_bit() {
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_tls(HOST, origin_ca_tls());
+    let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+    let mut domain = Domain::build(HOST.to_string())
+        .unwrap()
+        .with_detectors(check_result::DEFAULT | check_result::CF_ORIGIN_CA);
+    domain.verify_domain_with(cf_ips(), &probe).await.unwrap();
+    assert_eq!(domain.check_result, check_result::CF_ORIGIN_CA);
+    assert!(!domain.has_cf_ssl());
+    assert_eq!(domain.get_status(), "Exposed origin");
+}
+
+#[tokio::test]
+async fn origin_ca_is_seen_when_the_page_fails() {
+    let transport = MockTransport::new().with_tls(HOST, origin_ca_tls());
+    let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+    let mut domain = Domain::build(HOST.to_string())
+        .unwrap()
+        .with_detectors(check_result::DEFAULT | check_result::CF_ORIGIN_CA);
+    domain.verify_domain_with(cf_ips(), &probe).await.unwrap();
+    assert!(!domain.is_unreachable);
+    assert_eq!(domain.get_status(), "Exposed origin");
+}
+
+#[tokio::test]
+async fn cf_trace_bit() {
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_response(TRACE_URL, HttpResponse::new(200).body(TRACE_BODY));
+    assert_eq!(check(transport).await.check_result, check_result::CF_TRACE);
+}
+
+#[tokio::test]
+async fn every_bit() {
+    let transport = MockTransport::new()
+        .with_response(
+            URL,
+            HttpResponse::new(200)
+                .header("server", "cloudflare")
+                .header("cf-ray", "7f1a2b3c4d5e6f70-AMS")
+                .header("cf-cache-status", "DYNAMIC")
+                .remote_ip("104.16.1.1".parse().unwrap()),
+        )
+        .with_response(TRACE_URL, HttpResponse::new(200).body(TRACE_BODY))
+        .with_tls(HOST, cf_tls());
+    let domain = check(transport).await;
+    assert_eq!(
+        domain.check_result,
+        check_result::DEFAULT | check_result::CF_TRACE
+    );
+    assert_eq!(domain.get_status(), "CF detected");
+}
+
+#[tokio::test]
+async fn disabled_detectors_dont_set_bits() {
+    let transport = MockTransport::new()
+        .with_response(
+            URL,
+            HttpResponse::new(200)
+                .header("server", "cloudflare")
+                .header("cf-ray", "7f1a2b3c4d5e6f70-AMS")
+                .remote_ip("104.16.1.1".parse().unwrap()),
+        )
+        .with_tls(HOST, cf_tls());
+    let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+    let mut domain = Domain::build(HOST.to_string())
+        .unwrap()
+        .with_detectors(check_result::CF_IP | check_result::CF_SSL);
+    domain.verify_domain_with(cf_ips(), &probe).await.unwrap();
+    assert_eq!(
+        domain.check_result,
+        check_result::CF_IP | check_result::CF_SSL
+    );
+}
+
+#[tokio::test]
+async fn unreachable_domain() {
+    let domain = check(MockTransport::new()).await;
+    assert!(domain.is_unreachable);
+    assert_eq!(domain.check_result, check_result::EMPTY);
+    assert_eq!(domain.get_status(), "Unreachable");
+}
+
+#[tokio::test]
+async fn checker_loads_ranges_through_the_transport() {
+    let transport = MockTransport::new()
+        .with_response(IPS_V4_URL, HttpResponse::new(200).body("104.16.0.0/13"))
+        .with_response(IPS_V6_URL, HttpResponse::new(200).body("2606:4700::/32"))
+        .with_response(
+            URL,
+            HttpResponse::new(200).remote_ip("104.16.1.1".parse().unwrap()),
+        )
+        .with_response(
+            "http://example.net",
+            HttpResponse::new(200).remote_ip("192.0.2.1".parse().unwrap()),
+        );
+    let mut checker = Checker::builder()
+        .targets("example.com\nexample.net\nexample.org")
+        .transport(Arc::new(transport))
+        .build()
+        .await
+        .unwrap();
+    let summary = checker.check().await.unwrap();
+    assert_eq!(
+        (summary.detected, summary.not_detected, summary.unreachable),
+        (1, 1, 1)
+    );
+    assert_eq!(checker.detected()[0].domain.name, HOST);
+    assert_eq!(checker.unreachable()[0].domain.name, "example.org");
+}
+
+#[tokio::test]
+async fn checker_tells_exposed_origins_apart() {
+    let transport = MockTransport::new()
+        // The origin forwards CF's headers of an earlier hop, but serves its Origin CA certificate.
+        .with_response(
+            URL,
+            HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"),
+        )
+        .with_tls(HOST, origin_ca_tls())
+        .with_response(
+            "http://example.net",
+            HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"),
+        );
+    let mut checker = Checker::builder()
+        .targets("example.com\nexample.net")
+        .detectors(check_result::DEFAULT | check_result::CF_ORIGIN_CA)
+        .cf_ips(cf_ips())
+        .transport(Arc::new(transport))
+        .build()
+        .await
+        .unwrap();
+    let summary = checker.check().await.unwrap();
+    assert_eq!(
+        (summary.detected, summary.exposed, summary.not_detected),
+        (1, 1, 0)
+    );
+    assert_eq!(checker.exposed()[0].domain.name, HOST);
+    assert_eq!(checker.detected()[0].domain.name, "example.net");
+    assert_eq!(checker.exposed()[0].domain.get_status(), "Exposed origin");
+}
+
+#[tokio::test]
+async fn checker_reports_each_port() {
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_response(
+            "https://example.com:8443",
+            HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"),
+        )
+        .with_tls("example.com:8443", cf_tls());
+    let mut checker = Checker::builder()
+        .targets(HOST)
+        .ports(&[80, 8443, 2096])
+        .cf_ips(cf_ips())
+        .transport(Arc::new(transport))
+        .build()
+        .await
+        .unwrap();
+    let summary = checker.check().await.unwrap();
+    assert_eq!(
+        (summary.detected, summary.not_detected, summary.unreachable),
+        (1, 1, 1)
+    );
+    let detected = &checker.detected()[0].domain;
+    assert_eq!(detected.target(), "example.com:8443");
+    assert_eq!(
+        detected.check_result,
+        check_result::CF_RAY_HEADER | check_result::CF_SSL
+    );
+    assert!(summary.durations.contains_key("example.com:2096"));
+}
+
+#[tokio::test]
+async fn checker_checks_each_url_once() {
+    let checker = Checker::builder()
+        .targets(
+            "example.com:80
+example.net
+example.net:8443",
+        )
+        .ports(&[443, 8443, 443])
+        .cf_ips(cf_ips())
+        .transport(Arc::new(MockTransport::new()))
+        .build()
+        .await
+        .unwrap();
+    let urls: Vec<String> = checker
+        .reports()
+        .iter()
+        .map(|report| report.domain.url())
+        .collect();
+    assert_eq!(
+        urls,
+        vec![
+            "http://example.com",
+            "https://example.net",
+            "https://example.net:8443"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn checker_tells_schemes_apart() {
+    let transport = MockTransport::new()
+        .with_response(URL, HttpResponse::new(200))
+        .with_response(
+            "https://example.com",
+            HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"),
+        );
+    let mut checker = Checker::builder()
+        .targets("example.com\nexample.com:443\nhttps://*.example.com")
+        .cf_ips(cf_ips())
+        .transport(Arc::new(transport))
+        .build()
+        .await
+        .unwrap();
+    let summary = checker.check().await.unwrap();
+    assert_eq!(checker.rejected().len(), 1);
+    let targets: Vec<String> = checker
+        .reports()
+        .iter()
+        .map(|report| report.domain.target())
+        .collect();
+    assert_eq!(targets, vec![HOST, "https://example.com"]);
+    assert_eq!(summary.durations.len(), 2);
+    assert_eq!(checker.detected()[0].domain.target(), "https://example.com");
+}
+
+#[tokio::test]
+async fn checker_reports_each_path() {
+    let transport = MockTransport::new()
+        .with_response(
+            "http://example.com/app?id=1",
+            HttpResponse::new(200).header("cf-ray", "7f1a2b3c4d5e6f70-AMS"),
+        )
+        .with_response("http://example.com/static/", HttpResponse::new(200))
+        .with_response(TRACE_URL, HttpResponse::new(200).body(TRACE_BODY));
+    let mut checker = Checker::builder()
+        .targets("example.com/app?id=1\nhttp://example.com/static/#top")
+        .detectors(check_result::DEFAULT | check_result::CF_TRACE)
+        .cf_ips(cf_ips())
+        .transport(Arc::new(transport))
+        .build()
+        .await
+        .unwrap();
+    let summary = checker.check().await.unwrap();
+    assert_eq!((summary.detected, summary.not_detected), (2, 0));
+    let reports = checker.reports();
+    assert_eq!(reports[0].domain.target(), "example.com/app?id=1");
+    assert_eq!(
+        reports[0].domain.check_result,
+        check_result::CF_RAY_HEADER | check_result::CF_TRACE
+    );
+    assert_eq!(reports[1].domain.target(), "example.com/static/");
+    assert_eq!(reports[1].domain.check_result, check_result::CF_TRACE);
+}
+
+#[tokio::test]
+async fn checker_fails_on_broken_range_lists() {
+    for (status, body) in [(503, "104.16.0.0/13"), (200, "<html>Maintenance</html>")] {
+        let transport = MockTransport::new()
+            .with_response(IPS_V4_URL, HttpResponse::new(status).body(body))
+            .with_response(IPS_V6_URL, HttpResponse::new(200).body("2606:4700::/32"));
+        let built = Checker::builder()
+            .targets(HOST)
+            .transport(Arc::new(transport))
+            .build()
+            .await;
+        assert!(built.is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! A local server which answers like CF's edge or like a bare origin does.
+//! It serves plain HTTP and HTTPS on the same port, telling them apart by the first byte a client sends.
+//! Its certificate is issued by a self-signed CA, so checks have to trust `ca_certificate` explicitly.
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+};
+
+/// The first byte of a TLS handshake record.
+const TLS_HANDSHAKE: u8 = 0x16;
+
+/// A body of CF's `/cdn-cgi/trace` endpoint.
+pub const TRACE_BODY: &str = "fl=123f45\nh=example.com\nip=127.0.0.1\nts=1700000000.123\nvisit_scheme=http\nuag=cfd\ncolo=AMS\nhttp=http/1.1\nloc=NL\ntls=off\nsni=off\nwarp=off\ngateway=off\n";
+
+/// A body of CF's challenge page.
+pub const CHALLENGE_BODY: &str = "<!DOCTYPE html><html><head><title>Just a moment...</title></head><body><script>window._cf_chl_opt={cType: 'non-interactive'};</script><script src=\"/cdn-cgi/challenge-platform/h/b/orchestrate/chl_page/v1\"></script></body></html>";
+
+/// What the server answers with.
+#[derive(Debug, Clone)]
+pub struct ServerProfile {
+    /// The status code of pages other than `/cdn-cgi/trace`.
+    pub status: u16,
+    /// Headers of pages other than `/cdn-cgi/trace`.
+    pub headers: Vec<(String, String)>,
+    /// The body of pages other than `/cdn-cgi/trace`.
+    pub body: String,
+    /// If it's set, `/cdn-cgi/trace` is answered as CF does.
+    pub trace: bool,
+    /// The common name of the CA which issues the server certificate.
+    pub issuer: String,
+}
+
+impl ServerProfile {
+    /// Answers like CF's edge does for a proxied hostname.
+    pub fn cloudflare() -> Self {
+        Self {
+            status: 200,
+            headers: vec![
+                ("server".to_string(), "cloudflare".to_string()),
+                ("cf-ray".to_string(), "7f1a2b3c4d5e6f70-AMS".to_string()),
+                ("cf-cache-status".to_string(), "DYNAMIC".to_string()),
+            ],
+            body: "<html><head><title>Example</title></head></html>".to_string(),
+            trace: true,
+            issuer: "Cloudflare Inc ECC CA-3".to_string(),
+        }
+    }
+
+    /// Answers like CF's edge does when it challenges a visitor.
+    pub fn challenge() -> Self {
+        let mut profile = ServerProfile::cloudflare();
+        profile.status = 403;
+        profile
+            .headers
+            .push(("cf-mitigated".to_string(), "challenge".to_string()));
+        profile.body = CHALLENGE_BODY.to_string();
+        profile
+    }
+
+    /// Answers like an origin without CF in front of it does.
+    pub fn origin() -> Self {
+        Self {
+            status: 200,
+            headers: vec![("server".to_string(), "nginx".to_string())],
+            body: "<html><head><title>Example</title></head></html>".to_string(),
+            trace: false,
+            issuer: "Example Origin CA".to_string(),
+        }
+    }
+
+    /// Answers like an exposed origin which serves a CF Origin CA certificate does.
+    pub fn origin_ca() -> Self {
+        Self {
+            issuer: "CloudFlare Origin SSL Certificate Authority".to_string(),
+            ..ServerProfile::origin()
+        }
+    }
+
+    /// Replaces a header or adds it if it's missing.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Removes a header.
+    pub fn without_header(mut self, name: &str) -> Self {
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        self
+    }
+}
+
+/// A running server. It's stopped when the test process exits.
+pub struct TestServer {
+    addr: SocketAddr,
+    ca_certificate: Vec<u8>,
+    ca_pem: String,
+}
+
+impl TestServer {
+    /// Starts a server for `host` on a free loopback port.
+    pub fn start(host: &str, profile: ServerProfile) -> Self {
+        let (tls, ca_certificate, ca_pem) = tls_config(host, &profile.issuer);
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind the test server");
+        let addr = listener.local_addr().unwrap();
+        let profile = Arc::new(profile);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tls = tls.clone();
+                let profile = profile.clone();
+                thread::spawn(move || serve(stream, tls, &profile));
+            }
+        });
+        Self {
+            addr,
+            ca_certificate,
+            ca_pem,
+        }
+    }
+
+    /// Returns the address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the DER encoded CA certificate which issued the server certificate.
+    pub fn ca_certificate(&self) -> Vec<u8> {
+        self.ca_certificate.clone()
+    }
+
+    /// Returns the CA certificate PEM encoded, as CA bundles keep it.
+    pub fn ca_pem(&self) -> &str {
+        &self.ca_pem
+    }
+}
+
+/// Issues a certificate for `host` by a fresh CA named `issuer`.
+/// Returns a server config with it and the CA certificate, DER and PEM encoded.
+fn tls_config(host: &str, issuer: &str) -> (Arc<ServerConfig>, Vec<u8>, String) {
+    let mut ca_params = CertificateParams::new(vec![]);
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CountryName, "US");
+    name.push(DnType::OrganizationName, issuer);
+    name.push(DnType::CommonName, issuer);
+    ca_params.distinguished_name = name;
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = Certificate::from_params(ca_params).unwrap();
+    let leaf = Certificate::from_params(CertificateParams::new(vec![host.to_string()])).unwrap();
+    let leaf_der = leaf.serialize_der_with_signer(&ca).unwrap();
+    let ca_der = ca.serialize_der().unwrap();
+    let ca_pem = ca.serialize_pem().unwrap();
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![
+                rustls::Certificate(leaf_der),
+                rustls::Certificate(ca_der.clone()),
+            ],
+            rustls::PrivateKey(leaf.serialize_private_key_der()),
+        )
+        .unwrap();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    (Arc::new(config), ca_der, ca_pem)
+}
+
+fn serve(stream: TcpStream, tls: Arc<ServerConfig>, profile: &ServerProfile) {
+    let mut first = [0u8; 1];
+    if stream.peek(&mut first).is_err() {
+        return;
+    }
+    if first[0] == TLS_HANDSHAKE {
+        let conn = ServerConnection::new(tls).unwrap();
+        let mut stream = StreamOwned::new(conn, stream);
+        respond(&mut stream, profile);
+        stream.conn.send_close_notify();
+        let _ = stream.flush();
+    } else {
+        let mut stream = stream;
+        respond(&mut stream, profile);
+    }
+}
+
+/// Reads a request and writes the response. Errors mean the client went away and are ignored.
+fn respond(stream: &mut impl ReadWrite, profile: &ServerProfile) {
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => request.extend_from_slice(&buf[..read]),
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, headers, body) = match path {
+        "/cdn-cgi/trace" if profile.trace => (200, vec![], TRACE_BODY.to_string()),
+        "/cdn-cgi/trace" => (404, vec![], String::new()),
+        _ => (
+            profile.status,
+            profile.headers.clone(),
+            profile.body.clone(),
+        ),
+    };
+    let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason(status));
+    for (name, value) in headers {
+        response += &format!("{}: {}\r\n", name, value);
+    }
+    response += &format!(
+        "content-type: text/html\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        403 => "Forbidden",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+trait ReadWrite: Read + Write {}
+
+impl<T: Read + Write> ReadWrite for T {}
@@ -0,0 +1,287 @@
+//! End-to-end checks against local servers which answer like CF's edge or like bare origins.
+//! The servers are reached through resolver overrides and trusted through extra root certificates.
+
+mod common;
+
+use cfd::{
+    cf_ips::CFIPs,
+    checker::Checker,
+    domain::{check_result, Domain, ResponseKind},
+    probe::{ClientOptions, Probe, ProbePolicy},
+};
+use common::{ServerProfile, TestServer};
+use std::{sync::Arc, time::Duration};
+
+/// CF's ranges with the loopback network in them, so local servers count as CF's edge.
+fn cf_ips_with_loopback() -> CFIPs {
+    CFIPs::parse("127.0.0.0/8\n104.16.0.0/13")
+}
+
+fn cf_ips_without_loopback() -> CFIPs {
+    CFIPs::parse("104.16.0.0/13")
+}
+
+fn policy() -> ProbePolicy {
+    ProbePolicy {
+        timeout: Duration::from_secs(5),
+        ..Default::default()
+    }
+}
+
+/// Checks `host` served by `server`, trusting its CA, with every detector enabled.
+async fn check(host: &str, server: &TestServer, cf_ips: CFIPs, trust: bool) -> Domain {
+    let mut builder = Checker::builder()
+        .targets(host)
+        .cf_ips(cf_ips)
+        .probe_policy(policy())
+        .detectors(check_result::DEFAULT | check_result::CF_TRACE)
+        .resolve(host, server.addr());
+    if trust {
+        builder = builder.root_certificate(server.ca_certificate());
+    }
+    let mut checker = builder.build().await.unwrap();
+    let summary = checker.check().await.unwrap();
+    assert!(summary.errors.is_empty(), "{:?}", summary.errors);
+    checker.into_reports().remove(0).domain
+}
+
+#[tokio::test]
+async fn cf_edge_sets_every_bit() {
+    let host = "edge.example.com";
+    let server = TestServer::start(host, ServerProfile::cloudflare());
+    let domain = check(host, &server, cf_ips_with_loopback(), true).await;
+    assert!(!domain.is_unreachable);
+    assert_eq!(
+        domain.check_result,
+        check_result::DEFAULT | check_result::CF_TRACE
+    );
+    assert_eq!(domain.response_kind, ResponseKind::Normal);
+    assert_eq!(domain.alpn.as_deref(), Some("http/1.1"));
+}
+
+#[tokio::test]
+async fn each_header_sets_its_bit() {
+    let cases = [
+        ("cf-ray", check_result::CF_RAY_HEADER),
+        ("cf-cache-status", check_result::CF_CACHE_STATUS_HEADER),
+        ("server", check_result::CF_SERVER),
+    ];
+    for (index, (header, bit)) in cases.into_iter().enumerate() {
+        let host = format!("header{}.example.com", index);
+        let mut profile = ServerProfile::origin().without_header("server");
+        profile = match header {
+            "server" => profile.header("server", "cloudflare"),
+            _ => profile.header(header, "HIT"),
+        };
+        let server = TestServer::start(&host, profile);
+        let domain = check(&host, &server, cf_ips_without_loopback(), true).await;
+        assert_eq!(domain.check_result, bit, "{}", header);
+    }
+}
+
+#[tokio::test]
+async fn challenge_page_is_classified() {
+    let host = "challenge.example.com";
+    let server = TestServer::start(host, ServerProfile::challenge());
+    let domain = check(host, &server, cf_ips_with_loopback(), true).await;
+    assert_eq!(domain.response_kind, ResponseKind::Challenge);
+    assert_eq!(domain.get_status(), "CF detected");
+}
+
+#[tokio::test]
+async fn bare_origin_sets_no_bits() {
+    let host = "origin.example.com";
+    let server = TestServer::start(host, ServerProfile::origin());
+    let domain = check(host, &server, cf_ips_without_loopback(), true).await;
+    assert!(!domain.is_unreachable);
+    assert_eq!(domain.check_result, check_result::EMPTY);
+    assert_eq!(domain.get_status(), "CF not detected");
+}
+
+#[tokio::test]
+async fn untrusted_certificate_doesnt_set_cf_ssl_bit() {
+    let host = "untrusted.example.com";
+    let server = TestServer::start(host, ServerProfile::cloudflare());
+    let domain = check(host, &server, cf_ips_with_loopback(), false).await;
+    assert!(!domain.has_cf_ssl());
+    assert!(domain.has_cf_ray_header());
+}
+
+#[tokio::test]
+async fn untrusted_certificate_is_inspected() {
+    let host = "inspected.example.com";
+    let server = TestServer::start(host, ServerProfile::cloudflare());
+    let mut checker = Checker::builder()
+        .targets(host)
+        .cf_ips(cf_ips_with_loopback())
+        .probe_policy(policy())
+        .resolve(host, server.addr())
+        .inspect_certificates(true)
+        .build()
+        .await
+        .unwrap();
+    checker.check().await.unwrap();
+    let domain = &checker.reports()[0].domain;
+    assert!(!domain.has_cf_ssl());
+    assert!(domain
+        .tls_error
+        .as_deref()
+        .unwrap()
+        .contains("UnknownIssuer"));
+    assert_eq!(domain.alpn.as_deref(), Some("http/1.1"));
+}
+
+#[tokio::test]
+async fn exposed_origin_with_origin_ca_certificate() {
+    let host = "exposed.example.com";
+    let server = TestServer::start(host, ServerProfile::origin_ca());
+    let mut checker = Checker::builder()
+        .targets(host)
+        .cf_ips(cf_ips_without_loopback())
+        .probe_policy(policy())
+        .detectors(check_result::DEFAULT | check_result::CF_ORIGIN_CA)
+        .resolve(host, server.addr())
+        .inspect_certificates(true)
+        .build()
+        .await
+        .unwrap();
+    checker.check().await.unwrap();
+    let domain = &checker.reports()[0].domain;
+    assert_eq!(domain.check_result, check_result::CF_ORIGIN_CA);
+    assert!(domain.tls_error.is_some());
+}
+
+#[tokio::test]
+async fn exposed_origin_on_an_https_target() {
+    let host = "exposed.example.com";
+    let server = TestServer::start(host, ServerProfile::origin_ca());
+    let mut checker = Checker::builder()
+        .targets(&format!("https://{}:{}", host, server.addr().port()))
+        .cf_ips(cf_ips_without_loopback())
+        .probe_policy(policy())
+        .detectors(check_result::DEFAULT | check_result::CF_ORIGIN_CA)
+        .resolve(host, server.addr())
+        .inspect_certificates(true)
+        .build()
+        .await
+        .unwrap();
+    checker.check().await.unwrap();
+    let domain = &checker.reports()[0].domain;
+    assert!(!domain.is_unreachable);
+    assert_eq!(domain.check_result, check_result::CF_ORIGIN_CA);
+    assert_eq!(domain.response_kind, ResponseKind::Normal);
+}
+
+#[tokio::test]
+async fn untrusted_certificate_fails_the_tls_probe() {
+    let host = "strict.example.com";
+    let server = TestServer::start(host, ServerProfile::cloudflare());
+    let domain = check(host, &server, cf_ips_with_loopback(), false).await;
+    assert!(domain.tls_error.is_some());
+    assert_eq!(domain.alpn, None);
+}
+
+#[tokio::test]
+async fn ca_file_is_trusted() {
+    let host = "ca-file.example.com";
+    let server = TestServer::start(host, ServerProfile::cloudflare());
+    let path = std::env::temp_dir().join(format!("cfd_ca_{}.pem", server.addr().port()));
+    std::fs::write(&path, server.ca_pem()).unwrap();
+    let certificates = ClientOptions::read_ca_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(certificates.len(), 1);
+    let mut builder = Checker::builder()
+        .targets(host)
+        .cf_ips(cf_ips_with_loopback())
+        .probe_policy(policy())
+        .resolve(host, server.addr())
+        .inspect_certificates(true);
+    for certificate in certificates {
+        builder = builder.root_certificate(certificate);
+    }
+    let mut checker = builder.build().await.unwrap();
+    checker.check().await.unwrap();
+    let domain = &checker.reports()[0].domain;
+    assert!(domain.has_cf_ssl());
+    assert_eq!(domain.tls_error, None);
+}
+
+#[tokio::test]
+async fn url_targets_on_arbitrary_ports() {
+    let host = "spectrum.example.com";
+    let server = TestServer::start(host, ServerProfile::cloudflare());
+    let port = server.addr().port();
+    for (target, cf_ssl) in [
+        (format!("https://{}:{}/", host, port), true),
+        (format!("http://{}:{}", host, port), false),
+    ] {
+        // The port isn't one of CF's HTTPS ones, so only the HTTPS target shows its scheme.
+        let expected = target.trim_end_matches('/').trim_start_matches("http://");
+        let mut checker = Checker::builder()
+            .targets(&target)
+            .cf_ips(cf_ips_without_loopback())
+            .probe_policy(policy())
+            .resolve(host, "127.0.0.1:0".parse().unwrap())
+            .root_certificate(server.ca_certificate())
+            .build()
+            .await
+            .unwrap();
+        checker.check().await.unwrap();
+        let domain = &checker.reports()[0].domain;
+        assert_eq!(domain.target(), expected);
+        assert!(domain.has_cf_ray_header(), "{}", target);
+        // The TLS probe goes to 443 for plain HTTP targets, where nothing listens.
+        assert_eq!(domain.has_cf_ssl(), cf_ssl, "{}", target);
+    }
+}
+
+#[tokio::test]
+async fn internationalized_domain_name() {
+    let server = TestServer::start("xn--bcher-kva.example", ServerProfile::cloudflare());
+    let host = "bücher.example";
+    let domain = check(host, &server, cf_ips_with_loopback(), true).await;
+    assert_eq!(domain.name, "xn--bcher-kva.example");
+    assert_eq!(domain.target(), host);
+    assert_eq!(
+        domain.check_result,
+        check_result::DEFAULT | check_result::CF_TRACE
+    );
+}
+
+#[tokio::test]
+async fn verify_domain_with_probe() {
+    let host = "probe.example.com";
+    let server = TestServer::start(host, ServerProfile::cloudflare());
+    let mut options = ClientOptions {
+        root_certificates: vec![server.ca_certificate()],
+        ..Default::default()
+    };
+    options.resolve.insert(host.to_string(), server.addr());
+    let probe = Probe::new(policy(), &options).unwrap();
+    let mut domain = Domain::build(host.to_string()).unwrap();
+    domain
+        .verify_domain_with(Arc::new(cf_ips_with_loopback()), &probe)
+        .await
+        .unwrap();
+    assert_eq!(domain.check_result, check_result::DEFAULT);
+}
+
+#[tokio::test]
+async fn unreachable_server() {
+    let host = "down.example.com";
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut checker = Checker::builder()
+        .targets(host)
+        .cf_ips(cf_ips_with_loopback())
+        .probe_policy(policy())
+        .resolve(host, ([127, 0, 0, 1], port).into())
+        .build()
+        .await
+        .unwrap();
+    let summary = checker.check().await.unwrap();
+    assert_eq!(summary.unreachable, 1);
+}
@@ -0,0 +1,81 @@
+//! Builds the library with each supported feature set and checks that the lean one
+//! doesn't pull in the dependencies of optional features.
+
+use std::process::Command;
+
+/// Feature sets the library has to build with.
+const FEATURE_SETS: &[&[&str]] = &[
+    &["--no-default-features"],
+    &["--no-default-features", "--features", "tls-check"],
+    &["--no-default-features", "--features", "blocking"],
+    &["--no-default-features", "--features", "cli"],
+    &["--no-default-features", "--features", "origin"],
+    &["--no-default-features", "--features", "public-suffix"],
+    &["--no-default-features", "--features", "confusables"],
+    &["--all-features"],
+];
+
+/// Dependencies which only optional features may pull in.
+const OPTIONAL_DEPENDENCIES: &[&str] = &[
+    "clap",
+    "prettytable-rs",
+    "rustls",
+    "rustls-pemfile",
+    "webpki-roots",
+    "x509-parser",
+    "hickory-resolver",
+    "sha2",
+    "publicsuffix",
+    "unicode-security",
+];
+
+fn cargo(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        // A separate target dir, as the one running the tests is locked.
+        .env(
+            "CARGO_TARGET_DIR",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/target/feature-matrix"),
+        )
+        .output()
+        .expect("Failed to run cargo");
+    assert!(
+        output.status.success(),
+        "cargo {} failed:\n{}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn builds_with_every_feature_set() {
+    for features in FEATURE_SETS {
+        let mut args = vec!["check", "--lib", "--bins", "--quiet"];
+        args.extend_from_slice(features);
+        cargo(&args);
+    }
+}
+
+#[test]
+fn lean_library_has_no_optional_dependencies() {
+    let tree = cargo(&[
+        "tree",
+        "--no-default-features",
+        "--edges",
+        "normal",
+        "--prefix",
+        "none",
+        "--format",
+        "{p}",
+    ]);
+    for line in tree.lines() {
+        let name = line.split_whitespace().next().unwrap_or_default();
+        assert!(
+            !OPTIONAL_DEPENDENCIES.contains(&name),
+            "{} is pulled in without features",
+            line
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Offline direct-to-origin verification through a mock transport.
+
+use cfd::{
+    domain::{Domain, TlsInfo},
+    origin::OriginVerification,
+    probe::{Probe, ProbePolicy},
+    transport::{HttpResponse, MockTransport},
+};
+use std::{net::IpAddr, sync::Arc};
+
+const HOST: &str = "example.com";
+const PAGE_URL: &str = "https://example.com";
+
+fn ip() -> IpAddr {
+    "192.0.2.10".parse().unwrap()
+}
+
+fn tls(certificate: &[u8]) -> TlsInfo {
+    TlsInfo {
+        certificate: Some(certificate.to_vec()),
+        ..Default::default()
+    }
+}
+
+fn page(title: &str) -> HttpResponse {
+    HttpResponse::new(200).body(format!("<html><title>{}</title></html>", title))
+}
+
+/// A transport where the proxied host serves the edge certificate and the "Example" page.
+fn proxied() -> MockTransport {
+    MockTransport::new()
+        .with_tls(HOST, tls(b"edge"))
+        .with_response(PAGE_URL, page("Example"))
+}
+
+async fn verify(transport: MockTransport) -> Result<OriginVerification, String> {
+    verify_target(HOST, transport).await
+}
+
+async fn verify_target(
+    target: &str,
+    transport: MockTransport,
+) -> Result<OriginVerification, String> {
+    let probe = Probe::with_transport(ProbePolicy::default(), Arc::new(transport));
+    Domain::build(target.to_string())
+        .unwrap()
+        .verify_origin_with(ip(), &probe)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tokio::test]
+async fn origin_serving_the_same_page_is_verified() {
+    let transport = proxied()
+        .with_direct_tls(ip(), HOST, tls(b"origin"))
+        .with_direct_response(ip(), PAGE_URL, page("Example"));
+    let verification = verify(transport).await.unwrap();
+    let comparison = verification.comparison();
+    assert!(!comparison.same_certificate);
+    assert!(comparison.same_status && comparison.same_title && comparison.same_body);
+    assert!(verification.is_origin());
+}
+
+#[tokio::test]
+async fn origin_serving_the_same_certificate_is_verified() {
+    let transport = proxied()
+        .with_direct_tls(ip(), HOST, tls(b"edge"))
+        .with_direct_response(ip(), PAGE_URL, HttpResponse::new(403));
+    let verification = verify(transport).await.unwrap();
+    assert!(verification.comparison().same_certificate);
+    assert!(verification.is_origin());
+}
+
+#[tokio::test]
+async fn other_site_is_not_an_origin() {
+    let transport = proxied()
+        .with_direct_tls(ip(), HOST, tls(b"default"))
+        .with_direct_response(ip(), PAGE_URL, page("Welcome to nginx!"));
+    let verification = verify(transport).await.unwrap();
+    assert_eq!(
+        verification.origin.title.as_deref(),
+        Some("Welcome to nginx!")
+    );
+    assert!(!verification.is_origin());
+}
+
+#[tokio::test]
+async fn silent_ip_fails_verification() {
+    let error = verify(proxied()).await.unwrap_err();
+    assert_eq!(error, "192.0.2.10 doesn't answer for example.com");
+}
+
+#[tokio::test]
+async fn port_and_path_of_the_target_are_probed() {
+    let url = "https://example.com:8443/app";
+    let transport = MockTransport::new()
+        .with_tls("example.com:8443", tls(b"edge"))
+        .with_response(url, page("App"))
+        // The origin serves something else on 443 and at the root.
+        .with_direct_tls(ip(), HOST, tls(b"default"))
+        .with_direct_tls(ip(), "example.com:8443", tls(b"edge"))
+        .with_direct_response(ip(), PAGE_URL, page("Example"))
+        .with_direct_response(ip(), url, page("App"));
+    let verification = verify_target("example.com:8443/app", transport)
+        .await
+        .unwrap();
+    let comparison = verification.comparison();
+    assert!(comparison.same_certificate && comparison.same_title);
+}